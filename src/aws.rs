@@ -0,0 +1,317 @@
+use object_store::aws::{
+    AmazonS3, AmazonS3Builder, AmazonS3ConfigKey, AwsCredentialProvider, Checksum,
+};
+
+use crate::model::Params;
+use crate::{Error, ImageThumbs, ThumbsResult};
+
+/// Static credentials of an AWS IAM user or role.
+#[derive(Debug, Clone, Default)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Only needed for temporary credentials
+    pub session_token: Option<String>,
+}
+
+/// Connection details of an S3-compatible service other than AWS, e.g., MinIO or Ceph RGW.
+#[derive(Debug, Clone, Default)]
+pub struct S3Endpoint {
+    /// URL of the service, e.g., `http://localhost:9000`
+    pub url: String,
+    /// Region used to sign the requests, e.g., `us-east-1` for MinIO
+    pub region: String,
+    /// Addresses objects as `<url>/<bucket>/<key>` instead of `<bucket>.<url>/<key>`, as
+    /// expected by most self-hosted services
+    pub path_style: bool,
+}
+
+impl ImageThumbs<AmazonS3> {
+    /// Creates new ImageThumbs instance connected to AWS S3 using the environment variables
+    /// `AWS_BUCKET`, `AWS_REGION`, `AWS_ACCESS_KEY_ID`, and `AWS_SECRET_ACCESS_KEY` to connect to
+    /// S3.
+    ///
+    /// Without access keys, a role is assumed via web identity if `AWS_WEB_IDENTITY_TOKEN_FILE`
+    /// and `AWS_ROLE_ARN` (and optionally `AWS_ROLE_SESSION_NAME`) are set, e.g., by IAM roles for
+    /// service accounts in EKS. Otherwise, the credentials of the ECS task or EC2 instance are
+    /// used.
+    ///
+    /// Fails with [`Error::MissingConfig`] if the bucket is not set, or only one of the access
+    /// key variables.
+    ///
+    /// Reads the config file to know which thumbnails to create, see [`ImageThumbs::new`].
+    ///
+    /// # Arguments
+    /// * `config` - Path to the config file from the crate root (the extension may be omitted)
+    pub async fn new_aws(config: &str) -> ThumbsResult<Self> {
+        Self::new_aws_with_settings(Self::settings(config)?).await
+    }
+
+    pub async fn new_aws_with_settings(settings: Vec<Params>) -> ThumbsResult<Self> {
+        let builder = AmazonS3Builder::from_env();
+        check_env(&builder, false)?;
+        let client = builder
+            .with_checksum_algorithm(Checksum::SHA256)
+            .with_client_options(Self::client_options())
+            .build()?;
+
+        Ok(Self::from_parts(client, settings))
+    }
+
+    /// Creates new ImageThumbs instance connected to an S3-compatible service, e.g., MinIO.
+    ///
+    /// The bucket and credentials are read from the same environment variables as in
+    /// [`ImageThumbs::new_aws`], the region and endpoint are taken from `endpoint`. As there is no
+    /// instance role to fall back to, the access key is required.
+    ///
+    /// # Arguments
+    /// * `endpoint` - URL, region, and addressing style of the service
+    ///
+    /// * `config` - Path to the config file from the crate root (the extension may be omitted)
+    pub async fn new_s3_compatible(endpoint: S3Endpoint, config: &str) -> ThumbsResult<Self> {
+        Self::new_s3_compatible_with_settings(endpoint, Self::settings(config)?).await
+    }
+
+    pub async fn new_s3_compatible_with_settings(
+        endpoint: S3Endpoint,
+        settings: Vec<Params>,
+    ) -> ThumbsResult<Self> {
+        let client_options =
+            Self::client_options().with_allow_http(endpoint.url.starts_with("http://"));
+        let builder = AmazonS3Builder::from_env();
+        // there are no instance credentials to fall back to outside of AWS
+        check_env(&builder, true)?;
+        let client = builder
+            .with_endpoint(endpoint.url)
+            .with_region(endpoint.region)
+            .with_virtual_hosted_style_request(!endpoint.path_style)
+            .with_client_options(client_options)
+            .build()?;
+
+        Ok(Self::from_parts(client, settings))
+    }
+
+    /// Creates new ImageThumbs instance connected to the given AWS S3 bucket using the given
+    /// credentials, instead of reading them from environment variables.
+    ///
+    /// Reads the config file to know which thumbnails to create, see [`ImageThumbs::new`].
+    ///
+    /// # Arguments
+    /// * `bucket` - Name of the bucket
+    ///
+    /// * `region` - Region of the bucket, e.g., `eu-west-1`
+    ///
+    /// * `credentials` - Access key of the IAM user or role
+    ///
+    /// * `config` - Path to the config file from the crate root (the extension may be omitted)
+    pub async fn new_aws_with_credentials(
+        bucket: &str,
+        region: &str,
+        credentials: AwsCredentials,
+        config: &str,
+    ) -> ThumbsResult<Self> {
+        Self::new_aws_with_credentials_and_settings(
+            bucket,
+            region,
+            credentials,
+            Self::settings(config)?,
+        )
+        .await
+    }
+
+    pub async fn new_aws_with_credentials_and_settings(
+        bucket: &str,
+        region: &str,
+        credentials: AwsCredentials,
+        settings: Vec<Params>,
+    ) -> ThumbsResult<Self> {
+        check_bucket_and_region(bucket, region)?;
+        if credentials.access_key_id.is_empty() || credentials.secret_access_key.is_empty() {
+            return Err(Error::MissingConfig("credentials"));
+        }
+
+        let mut builder = AmazonS3Builder::new()
+            .with_bucket_name(bucket)
+            .with_region(region)
+            .with_access_key_id(credentials.access_key_id)
+            .with_secret_access_key(credentials.secret_access_key);
+        if let Some(token) = credentials.session_token {
+            builder = builder.with_token(token);
+        }
+        let client = builder
+            .with_checksum_algorithm(Checksum::SHA256)
+            .with_client_options(Self::client_options())
+            .build()?;
+
+        Ok(Self::from_parts(client, settings))
+    }
+
+    /// Creates new ImageThumbs instance connected to the given AWS S3 bucket, fetching
+    /// credentials from the given provider, e.g., to assume a role with credentials obtained
+    /// through the AWS SDK.
+    ///
+    /// Reads the config file to know which thumbnails to create, see [`ImageThumbs::new`].
+    ///
+    /// # Arguments
+    /// * `bucket` - Name of the bucket
+    ///
+    /// * `region` - Region of the bucket, e.g., `eu-west-1`
+    ///
+    /// * `provider` - Source of (temporary) credentials, see [`AwsCredentialProvider`]
+    ///
+    /// * `config` - Path to the config file from the crate root (the extension may be omitted)
+    pub async fn new_aws_with_credential_provider(
+        bucket: &str,
+        region: &str,
+        provider: AwsCredentialProvider,
+        config: &str,
+    ) -> ThumbsResult<Self> {
+        Self::new_aws_with_credential_provider_and_settings(
+            bucket,
+            region,
+            provider,
+            Self::settings(config)?,
+        )
+        .await
+    }
+
+    pub async fn new_aws_with_credential_provider_and_settings(
+        bucket: &str,
+        region: &str,
+        provider: AwsCredentialProvider,
+        settings: Vec<Params>,
+    ) -> ThumbsResult<Self> {
+        check_bucket_and_region(bucket, region)?;
+        let client = AmazonS3Builder::new()
+            .with_bucket_name(bucket)
+            .with_region(region)
+            .with_credentials(provider)
+            .with_checksum_algorithm(Checksum::SHA256)
+            .with_client_options(Self::client_options())
+            .build()?;
+
+        Ok(Self::from_parts(client, settings))
+    }
+}
+
+/// Fails with [`Error::MissingConfig`] if the environment lacks the bucket, or has only half of
+/// an access key, instead of failing on the first request.
+fn check_env(builder: &AmazonS3Builder, require_credentials: bool) -> ThumbsResult<()> {
+    let is_set = |key| {
+        builder
+            .get_config_value(&key)
+            .is_some_and(|value| !value.is_empty())
+    };
+    if !is_set(AmazonS3ConfigKey::Bucket) {
+        return Err(Error::MissingConfig("bucket"));
+    }
+    let key_id = is_set(AmazonS3ConfigKey::AccessKeyId);
+    let secret = is_set(AmazonS3ConfigKey::SecretAccessKey);
+    if key_id != secret || (require_credentials && !key_id) {
+        return Err(Error::MissingConfig("credentials"));
+    }
+    Ok(())
+}
+
+fn check_bucket_and_region(bucket: &str, region: &str) -> ThumbsResult<()> {
+    if bucket.is_empty() {
+        return Err(Error::MissingConfig("bucket"));
+    }
+    if region.is_empty() {
+        return Err(Error::MissingConfig("region"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use object_store::aws::{AmazonS3Builder, AwsCredential};
+    use object_store::StaticCredentialProvider;
+
+    use super::check_env;
+    use crate::{AwsCredentials, Error, ImageThumbs};
+
+    #[test]
+    fn check_environment() {
+        let builder = AmazonS3Builder::new().with_bucket_name("bucket");
+        check_env(&builder, false).unwrap();
+        assert!(matches!(
+            check_env(&builder, true),
+            Err(Error::MissingConfig("credentials"))
+        ));
+        assert!(matches!(
+            check_env(&AmazonS3Builder::new(), false),
+            Err(Error::MissingConfig("bucket"))
+        ));
+
+        let builder = builder.with_access_key_id("key");
+        assert!(matches!(
+            check_env(&builder, false),
+            Err(Error::MissingConfig("credentials"))
+        ));
+        check_env(&builder.with_secret_access_key("secret"), true).unwrap();
+    }
+
+    #[tokio::test]
+    async fn explicit_credentials() {
+        let credentials = AwsCredentials {
+            access_key_id: "key".to_string(),
+            secret_access_key: "secret".to_string(),
+            session_token: None,
+        };
+        ImageThumbs::new_aws_with_credentials_and_settings(
+            "bucket",
+            "eu-west-1",
+            credentials.clone(),
+            Vec::new(),
+        )
+        .await
+        .unwrap();
+
+        let result = ImageThumbs::new_aws_with_credentials_and_settings(
+            "",
+            "eu-west-1",
+            credentials,
+            Vec::new(),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::MissingConfig("bucket"))));
+
+        let result = ImageThumbs::new_aws_with_credentials_and_settings(
+            "bucket",
+            "eu-west-1",
+            AwsCredentials::default(),
+            Vec::new(),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::MissingConfig("credentials"))));
+    }
+
+    #[tokio::test]
+    async fn credential_provider() {
+        let provider = Arc::new(StaticCredentialProvider::new(AwsCredential {
+            key_id: "key".to_string(),
+            secret_key: "secret".to_string(),
+            token: Some("session".to_string()),
+        }));
+        ImageThumbs::new_aws_with_credential_provider_and_settings(
+            "bucket",
+            "eu-west-1",
+            provider.clone(),
+            Vec::new(),
+        )
+        .await
+        .unwrap();
+
+        let result = ImageThumbs::new_aws_with_credential_provider_and_settings(
+            "bucket",
+            "",
+            provider,
+            Vec::new(),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::MissingConfig("region"))));
+    }
+}
@@ -0,0 +1,145 @@
+use object_store::path::Path;
+use object_store::ObjectStore;
+
+use crate::dir::DirSummary;
+use crate::model::ThumbReport;
+use crate::{Error, ImageThumbs, ThumbsResult};
+
+/// Outcome of a batch of images, e.g., a backfill, with the error of every image that failed
+/// instead of only the first one.
+#[derive(Debug, Default)]
+pub struct BatchOutcome {
+    /// Reports of the images of which thumbnails were created
+    pub succeeded: Vec<ThumbReport>,
+    /// Images that were skipped because all of their thumbnails already exist, or because they
+    /// are no images
+    pub skipped: Vec<Path>,
+    /// Images of which creating the thumbnails failed
+    pub failed: Vec<(Path, Error)>,
+    /// Whether the run was stopped before all images were processed, see
+    /// [`crate::DirOptions::cancel`]
+    pub cancelled: bool,
+}
+
+impl BatchOutcome {
+    /// Whether all images were processed or skipped without errors.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty() && !self.cancelled
+    }
+
+    /// Number of images the batch finished with, whether successful or not
+    pub(crate) fn finished(&self) -> usize {
+        self.succeeded.len() + self.skipped.len() + self.failed.len()
+    }
+
+    pub(crate) fn push(&mut self, image: Path, result: ThumbsResult<ThumbReport>) {
+        match result {
+            Ok(report) if report.skipped => self.skipped.push(image),
+            Ok(report) => self.succeeded.push(report),
+            Err(e) => self.failed.push((image, e)),
+        }
+    }
+}
+
+impl From<BatchOutcome> for DirSummary {
+    fn from(outcome: BatchOutcome) -> Self {
+        DirSummary {
+            processed: outcome.succeeded.len(),
+            skipped: outcome.skipped.len(),
+            failed: outcome.failed,
+            cancelled: outcome.cancelled,
+        }
+    }
+}
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Creates the thumbnails of all given images one after another, like
+    /// [`ImageThumbs::create_thumbs_with_report`]. An image that fails does not stop the batch;
+    /// its error is collected in the outcome instead.
+    ///
+    /// # Arguments
+    /// * `files` - images to create thumbnails for.
+    ///
+    /// * `dest_dir` - directory to store all created thumbnails, see
+    ///   [`ImageThumbs::create_thumbs`].
+    ///
+    /// * `force_override` - if `true` it will override already existent files with the same name.
+    ///   If false, it will preserve already existent files.
+    pub async fn create_thumbs_batch(
+        &self,
+        files: &[&str],
+        dest_dir: &str,
+        force_override: bool,
+    ) -> BatchOutcome {
+        let mut outcome = BatchOutcome::default();
+        for file in files {
+            let result = self
+                .create_thumbs_with_report(file, dest_dir, force_override)
+                .await;
+            match Path::parse(file) {
+                Ok(image) => outcome.push(image, result),
+                Err(e) => outcome.failed.push((Path::from(*file), e.into())),
+            }
+        }
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    use crate::model::{Mode, Params};
+    use crate::test_utils::{client_with_images, png};
+    use crate::{DirOptions, Error};
+
+    #[tokio::test]
+    async fn collect_batch_outcome() {
+        let settings = vec![Params::new("mini", (10, 10), Mode::Fit, 80)];
+        let client =
+            client_with_images(settings, &["images/penguin.png", "images/puffin.png"]).await;
+        let bytes = png();
+        client
+            .client
+            .put(
+                &Path::parse("images/broken.png").unwrap(),
+                bytes[..64].to_vec().into(),
+            )
+            .await
+            .unwrap();
+        client
+            .create_thumbs("images/puffin.png", "thumbs", false)
+            .await
+            .unwrap();
+
+        let outcome = client
+            .create_thumbs_batch(
+                &[
+                    "images/penguin.png",
+                    "images/puffin.png",
+                    "images/missing.png",
+                ],
+                "thumbs",
+                false,
+            )
+            .await;
+        assert_eq!(outcome.succeeded.len(), 1);
+        assert_eq!(outcome.succeeded[0].image, Path::from("images/penguin.png"));
+        assert_eq!(outcome.skipped, [Path::from("images/puffin.png")]);
+        assert!(matches!(
+            &outcome.failed[..],
+            [(image, Error::SourceNotFound(_))] if image == &Path::from("images/missing.png")
+        ));
+        assert!(!outcome.is_success());
+
+        // fails for the broken image, even without `continue_on_error`
+        let outcome = client
+            .create_thumbs_dir_batch(Some("images"), "thumbs", &DirOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(outcome.skipped.len(), 2);
+        assert_eq!(outcome.failed.len(), 1);
+        assert_eq!(outcome.failed[0].0, Path::from("images/broken.png"));
+    }
+}
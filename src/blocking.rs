@@ -0,0 +1,204 @@
+//! Synchronous wrapper around [`crate::ImageThumbs`] for CLI tools and code bases without an async
+//! runtime.
+//!
+//! Every call blocks the current thread until it has finished. The functions panic if they are
+//! called from within an async runtime; use the async API there instead.
+
+use bytes::Bytes;
+use image::ImageFormat;
+use object_store::aws::AmazonS3;
+use object_store::gcp::GoogleCloudStorage;
+use object_store::local::LocalFileSystem;
+use object_store::ObjectStore;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{Center, DirOptions, DirSummary, ImageHashes, ThumbsResult};
+
+/// Blocking version of [`crate::ImageThumbs`] that runs the async API on an internal runtime.
+pub struct ImageThumbs<T: ObjectStore> {
+    inner: crate::ImageThumbs<T>,
+    runtime: Runtime,
+}
+
+impl ImageThumbs<GoogleCloudStorage> {
+    /// See [`crate::ImageThumbs::new`].
+    pub fn new(config: &str) -> ThumbsResult<Self> {
+        let runtime = runtime()?;
+        let inner = runtime.block_on(crate::ImageThumbs::new(config))?;
+        Ok(Self { inner, runtime })
+    }
+}
+
+impl ImageThumbs<AmazonS3> {
+    /// See [`crate::ImageThumbs::new_aws`].
+    pub fn new_aws(config: &str) -> ThumbsResult<Self> {
+        let runtime = runtime()?;
+        let inner = runtime.block_on(crate::ImageThumbs::new_aws(config))?;
+        Ok(Self { inner, runtime })
+    }
+}
+
+impl ImageThumbs<LocalFileSystem> {
+    /// See [`crate::ImageThumbs::new_local`].
+    pub fn new_local(root: &str, config: &str) -> ThumbsResult<Self> {
+        let runtime = runtime()?;
+        let inner = runtime.block_on(crate::ImageThumbs::new_local(root, config))?;
+        Ok(Self { inner, runtime })
+    }
+}
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Wraps an instance created with the async API, e.g., with a custom store.
+    pub fn from_async(inner: crate::ImageThumbs<T>) -> ThumbsResult<Self> {
+        Ok(Self {
+            inner,
+            runtime: runtime()?,
+        })
+    }
+
+    /// Returns the wrapped async instance.
+    pub fn into_async(self) -> crate::ImageThumbs<T> {
+        self.inner
+    }
+
+    /// See [`crate::ImageThumbs::create_thumbs`].
+    pub fn create_thumbs(
+        &self,
+        file: &str,
+        dest_dir: &str,
+        force_override: bool,
+    ) -> ThumbsResult<ImageHashes> {
+        self.runtime
+            .block_on(self.inner.create_thumbs(file, dest_dir, force_override))
+    }
+
+    /// See [`crate::ImageThumbs::create_thumbs_man_center`].
+    pub fn create_thumbs_man_center(
+        &self,
+        file: &str,
+        dest_dir: &str,
+        force_override: bool,
+        center: impl Into<Center>,
+    ) -> ThumbsResult<ImageHashes> {
+        self.runtime.block_on(self.inner.create_thumbs_man_center(
+            file,
+            dest_dir,
+            force_override,
+            center,
+        ))
+    }
+
+    /// See [`crate::ImageThumbs::create_thumbs_from_bytes`].
+    pub fn create_thumbs_from_bytes(
+        &self,
+        bytes: impl Into<Bytes>,
+        dest_dir: &str,
+        image_name: &str,
+        format: ImageFormat,
+        force_override: bool,
+        center: impl Into<Center>,
+    ) -> ThumbsResult<ImageHashes> {
+        self.runtime.block_on(self.inner.create_thumbs_from_bytes(
+            bytes,
+            dest_dir,
+            image_name,
+            format,
+            force_override,
+            center,
+        ))
+    }
+
+    /// See [`crate::ImageThumbs::create_thumbs_from_file`].
+    pub fn create_thumbs_from_file(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        dest_dir: &str,
+        force_override: bool,
+    ) -> ThumbsResult<ImageHashes> {
+        self.runtime.block_on(
+            self.inner
+                .create_thumbs_from_file(path, dest_dir, force_override),
+        )
+    }
+
+    /// See [`crate::ImageThumbs::create_thumbs_dir`].
+    pub fn create_thumbs_dir(
+        &self,
+        directory: Option<&str>,
+        dest_dir: &str,
+        force_override: bool,
+    ) -> ThumbsResult<()> {
+        self.runtime.block_on(
+            self.inner
+                .create_thumbs_dir(directory, dest_dir, force_override),
+        )
+    }
+
+    /// See [`crate::ImageThumbs::create_thumbs_dir_with_options`].
+    pub fn create_thumbs_dir_with_options(
+        &self,
+        directory: Option<&str>,
+        dest_dir: &str,
+        options: &DirOptions,
+    ) -> ThumbsResult<DirSummary> {
+        self.runtime.block_on(
+            self.inner
+                .create_thumbs_dir_with_options(directory, dest_dir, options),
+        )
+    }
+
+    /// See [`crate::ImageThumbs::wait_for_replication`]. Replica uploads only make progress
+    /// while a call blocks, so this should be called before the instance is dropped.
+    pub fn wait_for_replication(&self) {
+        self.runtime.block_on(self.inner.wait_for_replication())
+    }
+}
+
+fn runtime() -> ThumbsResult<Runtime> {
+    Ok(Builder::new_current_thread().enable_all().build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::ImageFormat;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    use crate::blocking::ImageThumbs;
+    use crate::model::Params;
+    use crate::test_utils::png;
+
+    #[test]
+    fn create_thumbs_blocking() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client =
+            ImageThumbs::from_async(crate::ImageThumbs::from_parts(InMemory::new(), settings))
+                .unwrap();
+        let bytes = png();
+
+        client
+            .create_thumbs_from_bytes(
+                bytes,
+                "thumbs",
+                "penguin",
+                ImageFormat::Png,
+                false,
+                (0.5, 0.5),
+            )
+            .unwrap();
+        let client = client.into_async();
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(
+                client
+                    .client
+                    .head(&Path::parse("thumbs/penguin_mini.png").unwrap()),
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,100 @@
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use image::{DynamicImage, Rgba};
+use serde::Deserialize;
+
+use crate::{Error, ThumbsResult};
+
+/// Text drawn on top of a thumbnail, e.g., "DRAFT" or a copyright notice.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Caption {
+    pub(crate) text: String,
+    /// Path to a TrueType or OpenType font file
+    pub(crate) font: String,
+    /// Font size in pixels
+    pub(crate) size: f32,
+    /// RGBA color of the text
+    #[serde(default = "default_color")]
+    pub(crate) color: [u8; 4],
+    #[serde(default)]
+    pub(crate) position: Position,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Position {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    Center,
+}
+
+fn default_color() -> [u8; 4] {
+    [255, 255, 255, 255]
+}
+
+/// Draws the caption onto the thumbnail. The distance to the edges is half the font size.
+pub(crate) fn draw_caption(image: DynamicImage, caption: &Caption) -> ThumbsResult<DynamicImage> {
+    let bytes = std::fs::read(&caption.font).map_err(|e| Error::Font(e.to_string()))?;
+    let font = FontVec::try_from_vec(bytes).map_err(|e| Error::Font(e.to_string()))?;
+    let scale = PxScale::from(caption.size);
+    let scaled = font.as_scaled(scale);
+
+    let mut glyphs = Vec::with_capacity(caption.text.len());
+    let mut caret = 0.;
+    let mut previous = None;
+    for c in caption.text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        glyphs.push((id, caret));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+    }
+
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let (text_width, text_height) = (caret, scaled.height());
+    let margin = caption.size / 2.;
+    let (x, y) = match caption.position {
+        Position::TopLeft => (margin, margin),
+        Position::TopRight => (width - text_width - margin, margin),
+        Position::BottomLeft => (margin, height - text_height - margin),
+        Position::BottomRight => (width - text_width - margin, height - text_height - margin),
+        Position::Center => ((width - text_width) / 2., (height - text_height) / 2.),
+    };
+
+    let has_alpha = image.color().has_alpha();
+    let mut canvas = image.into_rgba8();
+    let color = caption.color.map(f32::from);
+    for (id, offset) in glyphs {
+        let glyph = id.with_scale_and_position(scale, point(x + offset, y + scaled.ascent()));
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue; // e.g., whitespace
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|glyph_x, glyph_y, coverage| {
+            let px = bounds.min.x as i64 + glyph_x as i64;
+            let py = bounds.min.y as i64 + glyph_y as i64;
+            if px < 0 || py < 0 || px >= canvas.width() as i64 || py >= canvas.height() as i64 {
+                return;
+            }
+            let alpha = coverage * color[3] / 255.;
+            let Rgba(pixel) = canvas.get_pixel_mut(px as u32, py as u32);
+            for channel in 0..3 {
+                pixel[channel] =
+                    (pixel[channel] as f32 * (1. - alpha) + color[channel] * alpha).round() as u8;
+            }
+            pixel[3] = (pixel[3] as f32 + (255. - pixel[3] as f32) * alpha).round() as u8;
+        });
+    }
+
+    let canvas = DynamicImage::from(canvas);
+    Ok(if has_alpha {
+        canvas
+    } else {
+        DynamicImage::from(canvas.into_rgb8())
+    })
+}
@@ -0,0 +1,170 @@
+use std::collections::{HashSet, VecDeque};
+
+use object_store::path::Path;
+use object_store::ObjectStore;
+
+use crate::model::ThumbReport;
+use crate::{Error, ImageThumbs, ThumbsResult};
+
+/// Number of finished images after which the checkpoint is saved again
+const CHECKPOINT_INTERVAL: usize = 100;
+
+/// Progress of a directory run that is persisted in the store, see
+/// [`crate::DirOptions::checkpoint`].
+pub(crate) struct Checkpoint<'a, T: ObjectStore> {
+    /// Instance whose retries and rate limit apply to the requests for the checkpoint
+    thumbs: &'a ImageThumbs<T>,
+    store: &'a dyn ObjectStore,
+    path: Path,
+    /// Last image that was finished, together with all images listed before it
+    last: Option<Path>,
+    /// Images that are not finished yet, in the order they are listed
+    pending: VecDeque<Path>,
+    /// Images that were finished before some image listed before them
+    finished: HashSet<Path>,
+    unsaved: usize,
+}
+
+impl<'a, T: ObjectStore> Checkpoint<'a, T> {
+    /// Reads the checkpoint stored at the given path, if a previous run left one.
+    pub(crate) async fn load(
+        thumbs: &'a ImageThumbs<T>,
+        store: &'a dyn ObjectStore,
+        path: &str,
+    ) -> ThumbsResult<Self> {
+        let path = Path::parse(path)?;
+        let last = match thumbs
+            .retrying(|| async { store.get(&path).await?.bytes().await })
+            .await
+        {
+            Ok(bytes) => {
+                let last = std::str::from_utf8(&bytes).map_err(|_| Error::Utf)?;
+                Some(Path::parse(last)?)
+            }
+            Err(object_store::Error::NotFound { .. }) => None,
+            Err(err) => Err(err)?,
+        };
+        Ok(Self {
+            thumbs,
+            store,
+            path,
+            last,
+            pending: VecDeque::new(),
+            finished: HashSet::new(),
+            unsaved: 0,
+        })
+    }
+
+    /// Sorts the images and drops the ones a previous run already finished.
+    pub(crate) fn remaining(&mut self, mut reports: Vec<ThumbReport>) -> Vec<ThumbReport> {
+        reports.sort_by(|a, b| a.image.cmp(&b.image));
+        reports.retain(|report| self.last.as_ref().is_none_or(|last| &report.image > last));
+        self.pending = reports.iter().map(|report| report.image.clone()).collect();
+        reports
+    }
+
+    /// Marks the image as finished, saving the checkpoint every [`CHECKPOINT_INTERVAL`] images.
+    pub(crate) async fn finish(&mut self, image: Path) -> ThumbsResult<()> {
+        self.finished.insert(image);
+        while let Some(next) = self.pending.front() {
+            if !self.finished.remove(next) {
+                break;
+            }
+            self.last = self.pending.pop_front();
+            self.unsaved += 1;
+        }
+        if self.unsaved >= CHECKPOINT_INTERVAL {
+            self.save().await?;
+        }
+        Ok(())
+    }
+
+    /// Stores the last finished image, so that an interrupted run can be resumed.
+    pub(crate) async fn save(&mut self) -> ThumbsResult<()> {
+        if let Some(last) = &self.last {
+            self.thumbs
+                .retrying(|| {
+                    self.store
+                        .put(&self.path, last.to_string().into_bytes().into())
+                })
+                .await?;
+        }
+        self.unsaved = 0;
+        Ok(())
+    }
+
+    /// Deletes the checkpoint after the run finished all images.
+    pub(crate) async fn clear(self) -> ThumbsResult<()> {
+        match self.thumbs.retrying(|| self.store.delete(&self.path)).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(err) => Err(err)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    use super::Checkpoint;
+    use crate::model::ThumbReport;
+    use crate::ImageThumbs;
+
+    #[tokio::test]
+    async fn advance_in_listing_order() {
+        let client = ImageThumbs::from_parts(InMemory::new(), Vec::new());
+        let store = &client.client;
+        let path = |path| Path::parse(path).unwrap();
+        store
+            .put(&path("jobs/backfill"), "images/a.png".into())
+            .await
+            .unwrap();
+
+        let mut checkpoint = Checkpoint::load(&client, store, "jobs/backfill")
+            .await
+            .unwrap();
+        let reports = [
+            "images/d.png",
+            "images/a.png",
+            "images/c.png",
+            "images/b.png",
+        ]
+        .into_iter()
+        .map(|image| ThumbReport {
+            image: path(image),
+            dest_dir: path("thumbs"),
+            skipped: false,
+            thumbs: Vec::new(),
+            source_hash: None,
+            nested: Path::default(),
+        })
+        .collect();
+        let remaining: Vec<_> = checkpoint
+            .remaining(reports)
+            .into_iter()
+            .map(|report| report.image)
+            .collect();
+        assert_eq!(
+            remaining,
+            [
+                path("images/b.png"),
+                path("images/c.png"),
+                path("images/d.png")
+            ]
+        );
+
+        checkpoint.finish(path("images/c.png")).await.unwrap();
+        assert_eq!(checkpoint.last, Some(path("images/a.png")));
+        checkpoint.finish(path("images/b.png")).await.unwrap();
+        assert_eq!(checkpoint.last, Some(path("images/c.png")));
+
+        checkpoint.save().await.unwrap();
+        let saved = store.get(&path("jobs/backfill")).await.unwrap();
+        assert_eq!(saved.bytes().await.unwrap().as_ref(), b"images/c.png");
+
+        checkpoint.clear().await.unwrap();
+        assert!(store.head(&path("jobs/backfill")).await.is_err());
+    }
+}
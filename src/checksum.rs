@@ -0,0 +1,124 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use md5::{Digest, Md5};
+use object_store::path::Path;
+use object_store::{Attribute, Attributes, ObjectMeta, PutPayload};
+
+/// Name of the object metadata entry the base64-encoded MD5 of the contents is stored in, in the
+/// format of the `Content-MD5` header
+pub(crate) const CONTENT_MD5_METADATA: &str = "content_md5";
+/// Store name of the errors of incomplete downloads, which are retried like network errors
+pub(crate) const CHECKSUM_STORE: &str = "checksum";
+
+/// Adds the MD5 of the payload to the attributes it is stored with, so that downloads can be
+/// verified, unless the caller already provided one.
+pub(crate) fn insert_checksum(attributes: &mut Attributes, payload: &PutPayload) {
+    let key = Attribute::Metadata(CONTENT_MD5_METADATA.into());
+    if attributes.get(&key).is_none() {
+        let mut md5 = Md5::new();
+        for chunk in payload {
+            md5.update(chunk);
+        }
+        attributes.insert(key, STANDARD.encode(md5.finalize()).into());
+    }
+}
+
+/// Checks that a downloaded object is complete, i.e., has the size reported by the store and
+/// matches the MD5 stored with it, if any. Mismatches are reported as generic storage errors, so
+/// that they are retried like other network errors.
+pub(crate) fn verify_checksum(
+    meta: &ObjectMeta,
+    attributes: &Attributes,
+    bytes: &Bytes,
+) -> object_store::Result<()> {
+    if bytes.len() != meta.size {
+        return Err(mismatch(
+            &meta.location,
+            format!("expected {} bytes, got {}", meta.size, bytes.len()),
+        ));
+    }
+    if let Some(expected) = attributes.get(&Attribute::Metadata(CONTENT_MD5_METADATA.into())) {
+        let actual = STANDARD.encode(Md5::digest(bytes));
+        if expected.as_ref() != actual {
+            return Err(mismatch(
+                &meta.location,
+                format!("expected MD5 {}, got {actual}", expected.as_ref()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn mismatch(path: &Path, message: String) -> object_store::Error {
+    object_store::Error::Generic {
+        store: CHECKSUM_STORE,
+        source: format!("{path} is corrupt: {message}").into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::{Attribute, Attributes, ObjectStore, PutOptions};
+
+    use super::CONTENT_MD5_METADATA;
+    use crate::model::{Mode, Params};
+    use crate::test_utils::png;
+    use crate::{Error, ImageThumbs};
+
+    #[tokio::test]
+    async fn verify_checksums() {
+        let settings = vec![Params::new("mini", (10, 10), Mode::Fit, 80)];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = png();
+        client
+            .upload("images/penguin.png", bytes.clone().into())
+            .await
+            .unwrap();
+        client
+            .create_thumbs("images/penguin.png", "thumbs", false)
+            .await
+            .unwrap();
+        let thumb = client
+            .client
+            .get(&Path::parse("thumbs/penguin_mini.png").unwrap())
+            .await
+            .unwrap();
+        assert!(thumb
+            .attributes
+            .get(&Attribute::Metadata(CONTENT_MD5_METADATA.into()))
+            .is_some());
+
+        let mut attributes = Attributes::new();
+        attributes.insert(
+            Attribute::Metadata(CONTENT_MD5_METADATA.into()),
+            "1B2M2Y8AsgTpgAmY7PhCfg==".into(),
+        );
+        let options = PutOptions {
+            attributes,
+            ..Default::default()
+        };
+        client
+            .client
+            .put_opts(
+                &Path::parse("images/puffin.png").unwrap(),
+                bytes.into(),
+                options,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            client
+                .download("images/puffin.png")
+                .await
+                .unwrap_err()
+                .inner(),
+            Error::Storage(object_store::Error::Generic {
+                store: "checksum",
+                ..
+            })
+        ));
+    }
+}
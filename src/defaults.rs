@@ -0,0 +1,60 @@
+use config::builder::DefaultState;
+use config::{Config, ConfigBuilder, ConfigError, Map, Value};
+
+use crate::ThumbsResult;
+
+/// Key of the fields that all presets inherit, unless they set the field themselves
+const DEFAULTS_KEY: &str = "defaults";
+
+/// Copies the fields of the `defaults` block into every preset stored under the given key, e.g.,
+/// `thumbs`, that does not set them itself.
+pub(crate) fn inherit_defaults(
+    mut builder: ConfigBuilder<DefaultState>,
+    config: &Config,
+    key: &str,
+    presets: &[Value],
+) -> ThumbsResult<ConfigBuilder<DefaultState>> {
+    let defaults = match config.get_table(DEFAULTS_KEY) {
+        Ok(defaults) => defaults,
+        Err(ConfigError::NotFound(_)) => Map::new(),
+        Err(e) => return Err(e.into()),
+    };
+    for (index, preset) in presets.iter().enumerate() {
+        let preset = preset.clone().into_table()?;
+        for (field, value) in &defaults {
+            if !preset.contains_key(field) {
+                builder = builder.set_override(format!("{key}[{index}].{field}"), value.clone())?;
+            }
+        }
+    }
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{Config, File, FileFormat};
+
+    use super::inherit_defaults;
+    use crate::model::{Mode, Params};
+
+    #[test]
+    fn inherit_defaults_block() {
+        let config = "defaults: { quality: 70, mode: crop }
+thumbs:
+  - { name: standard, size: [640, 480], mode: fit }
+  - { name: mini, quality: 90, size: [40, 40] }";
+        let builder = Config::builder().add_source(File::from_str(config, FileFormat::Yaml));
+        let config = builder.clone().build().unwrap();
+        let presets = config.get_array("thumbs").unwrap();
+        let settings: Vec<Params> = inherit_defaults(builder, &config, "thumbs", &presets)
+            .unwrap()
+            .build()
+            .unwrap()
+            .get("thumbs")
+            .unwrap();
+        assert_eq!(settings[0].quality, 70);
+        assert!(matches!(settings[0].mode, Mode::Fit));
+        assert_eq!(settings[1].quality, 90);
+        assert!(matches!(settings[1].mode, Mode::Crop));
+    }
+}
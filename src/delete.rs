@@ -0,0 +1,402 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use futures::{stream, StreamExt, TryStreamExt};
+use image::ImageFormat;
+use object_store::path::Path;
+use object_store::ObjectStore;
+
+use crate::model::Params;
+use crate::{ImageThumbs, ThumbsResult};
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Deletes one object from the store, e.g., an original image. Replicas are not touched.
+    ///
+    /// Depending on the store, deleting a missing object is either ignored or reported as an
+    /// error.
+    ///
+    /// # Arguments
+    /// * `path` - path of the object.
+    pub async fn delete(&self, path: &str) -> ThumbsResult<()> {
+        let path = Path::parse(path)?;
+        self.retrying(|| self.client.delete(&path)).await?;
+        Ok(())
+    }
+
+    /// Deletes several objects from the store, using bulk requests where the store supports
+    /// them, e.g., on S3. Replicas are not touched.
+    ///
+    /// # Arguments
+    /// * `paths` - paths of the objects.
+    pub async fn delete_all(&self, paths: &[&str]) -> ThumbsResult<()> {
+        let paths = paths
+            .iter()
+            .map(Path::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.retrying(|| {
+            self.client
+                .delete_stream(stream::iter(paths.clone().into_iter().map(Ok)).boxed())
+                .try_collect::<Vec<_>>()
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes the thumbnails of all presets for one image from the `dest_dir` directory, e.g.,
+    /// after the image itself was deleted.
+    ///
+    /// The thumbnail names are derived from the image name and the naming patterns of the
+    /// presets, so the image itself does not need to exist anymore. Missing thumbnails are
+    /// ignored. Replicas are not touched.
+    ///
+    /// # Arguments
+    /// * `file` - image to delete the thumbnails of, e.g., `penguin.jpg`.
+    ///
+    /// * `dest_dir` - directory the thumbnails were stored in.
+    ///
+    /// Returns the number of deleted thumbnails.
+    pub async fn delete_thumbs(&self, file: &str, dest_dir: &str) -> ThumbsResult<usize> {
+        let thumbs = self.thumb_paths(
+            &Path::parse(file)?,
+            &Self::resolve_dest_dir(dest_dir, file)?,
+            &Path::default(),
+        )?;
+
+        let mut deleted = 0;
+        for (_, path) in thumbs {
+            if self.retrying(|| self.client.head(&path)).await.is_err() {
+                continue; // some stores report deleting a missing object as an error, others don't
+            }
+            self.retrying(|| self.client.delete(&path)).await?;
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
+
+    /// Deletes all thumbnails in the `dest_dir` directory, including nested directories, e.g.,
+    /// when offboarding a tenant.
+    ///
+    /// # Arguments
+    /// * `dest_dir` - directory the thumbnails were stored in.
+    ///
+    /// * `source_dir` - if given, only thumbnails whose image does not exist anymore on this
+    ///   level of the source store are deleted. Thumbnails of existing images are kept.
+    ///
+    /// Returns the number of deleted thumbnails.
+    pub async fn delete_thumbs_dir(
+        &self,
+        dest_dir: &str,
+        source_dir: Option<&str>,
+    ) -> ThumbsResult<usize> {
+        let dest_dir = Path::parse(dest_dir)?;
+        let mut thumbs = Self::list_folder_recursive(&self.client, Some(&dest_dir)).await?;
+
+        if let Some(source_dir) = source_dir {
+            let images = Self::list_folder(self.source(), Some(&Path::parse(source_dir)?)).await?;
+            let existent: HashSet<Path> = images
+                .iter()
+                // objects that are not images have no thumbnails
+                .filter_map(|image| self.thumb_paths(image, &dest_dir, &Path::default()).ok())
+                .flatten()
+                .map(|(_, path)| path)
+                .collect();
+            thumbs.retain(|thumb| !existent.contains(thumb));
+        }
+
+        for thumb in &thumbs {
+            self.retrying(|| self.client.delete(thumb)).await?;
+        }
+        Ok(thumbs.len())
+    }
+
+    /// Deletes the thumbnails in the `dest_dir` directory, including nested directories, whose
+    /// image does not exist anymore on the given level of the source store.
+    ///
+    /// Unlike [`ImageThumbs::delete_thumbs_dir`], only objects that match the naming pattern of
+    /// a preset are considered thumbnails, so other files in `dest_dir` are never deleted.
+    ///
+    /// # Arguments
+    /// * `directory` - directory of the images the thumbnails were created for.
+    ///
+    /// * `dest_dir` - directory the thumbnails are stored in.
+    ///
+    /// Returns the paths of the deleted thumbnails.
+    pub async fn purge_orphans(
+        &self,
+        directory: Option<&str>,
+        dest_dir: &str,
+    ) -> ThumbsResult<Vec<Path>> {
+        let prefix = match directory {
+            Some(p) => Some(Path::parse(p)?),
+            None => None,
+        };
+        let images = Self::list_folder(self.source(), prefix.as_ref()).await?;
+        // the stems of the images by the presets they are created with, see
+        // [`ImageThumbs::presets_for`], including profiles no listed image is mapped to anymore
+        let mut preset_groups: Vec<(Arc<Vec<Params>>, HashSet<&str>)> =
+            vec![(self.presets(), HashSet::new())];
+        for (_, profile) in &self.prefix_profiles {
+            preset_groups.push((self.profile(profile)?, HashSet::new()));
+        }
+        for image in &images {
+            let Ok(stem) = Self::extract_stem(image) else {
+                continue;
+            };
+            let presets = self.presets_for(image)?;
+            match preset_groups
+                .iter_mut()
+                .find(|(group, _)| Arc::ptr_eq(group, &presets))
+            {
+                Some((_, stems)) => {
+                    stems.insert(stem);
+                }
+                None => preset_groups.push((presets, HashSet::from([stem]))),
+            }
+        }
+
+        let dest_dir = Path::parse(dest_dir)?;
+        let mut orphans = Vec::new();
+        for thumb in Self::list_folder_recursive(&self.client, Some(&dest_dir)).await? {
+            let Some(relative) = thumb.prefix_match(&dest_dir) else {
+                continue;
+            };
+            let relative = relative
+                .map(|part| part.as_ref().to_string())
+                .collect::<Vec<_>>()
+                .join("/");
+            let Some((thumb_stem, format)) =
+                relative.rsplit_once('.').and_then(|(stem, extension)| {
+                    Some((stem, ImageFormat::from_extension(extension)?))
+                })
+            else {
+                continue;
+            };
+            let mut is_thumb = false;
+            let mut has_image = false;
+            for (presets, stems) in &preset_groups {
+                for params in presets.iter() {
+                    if let Some(image_stem) = Self::image_stem_of(thumb_stem, params, format) {
+                        is_thumb = true;
+                        has_image |= stems.contains(image_stem);
+                    }
+                }
+            }
+            if is_thumb && !has_image {
+                orphans.push(thumb);
+            }
+        }
+
+        for orphan in &orphans {
+            self.retrying(|| self.client.delete(orphan)).await?;
+        }
+        Ok(orphans)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, ImageFormat};
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    use crate::image::encode;
+    use crate::model::{Mask, Mode, Params};
+    use crate::test_utils::{client_with_images, png};
+    use crate::ImageThumbs;
+
+    #[tokio::test]
+    async fn delete_thumbs() {
+        let settings = vec![
+            Params {
+                name: "mini".to_string(),
+                size: (10, 10),
+                ..Default::default()
+            },
+            Params {
+                name: "avatar".to_string(),
+                naming_pattern: Some("/{thumb_name}/{image_stem}".to_string()),
+                size: (20, 20),
+                mask: Some(Mask::Circle),
+                ..Default::default()
+            },
+        ];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = encode(&DynamicImage::new_rgb8(40, 20), ImageFormat::Jpeg, 80, None).unwrap();
+        client
+            .create_thumbs_from_bytes(
+                bytes,
+                "/thumbs",
+                "penguin",
+                ImageFormat::Jpeg,
+                false,
+                (0.5, 0.5),
+            )
+            .await
+            .unwrap();
+        let mini = Path::parse("thumbs/penguin_mini.jpg").unwrap();
+        let avatar = Path::parse("thumbs/avatar/penguin.png").unwrap();
+        client.client.head(&mini).await.unwrap();
+        client.client.head(&avatar).await.unwrap();
+
+        let deleted = client
+            .delete_thumbs("penguin.jpg", "/thumbs")
+            .await
+            .unwrap();
+        assert_eq!(deleted, 2);
+        assert!(client.client.head(&mini).await.is_err());
+        assert!(client.client.head(&avatar).await.is_err());
+
+        let deleted = client
+            .delete_thumbs("penguin.jpg", "/thumbs")
+            .await
+            .unwrap();
+        assert_eq!(deleted, 0);
+    }
+
+    #[tokio::test]
+    async fn delete_thumbs_dir() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            naming_pattern: Some("/{thumb_name}/{image_stem}".to_string()),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = png();
+        for name in ["penguin", "puffin"] {
+            client
+                .client
+                .put(
+                    &Path::parse(format!("images/{name}.png")).unwrap(),
+                    bytes.clone().into(),
+                )
+                .await
+                .unwrap();
+        }
+        client
+            .create_thumbs_dir(Some("images"), "/thumbs", false)
+            .await
+            .unwrap();
+
+        // the source of one thumbnail is deleted
+        client
+            .client
+            .delete(&Path::parse("images/puffin.png").unwrap())
+            .await
+            .unwrap();
+        let deleted = client
+            .delete_thumbs_dir("/thumbs", Some("images"))
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        let penguin = Path::parse("thumbs/mini/penguin.png").unwrap();
+        client.client.head(&penguin).await.unwrap();
+
+        let deleted = client.delete_thumbs_dir("/thumbs", None).await.unwrap();
+        assert_eq!(deleted, 1);
+        assert!(client.client.head(&penguin).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn purge_orphans() {
+        let settings = vec![
+            Params {
+                name: "mini".to_string(),
+                size: (10, 10),
+                ..Default::default()
+            },
+            Params {
+                name: "standard".to_string(),
+                naming_pattern: Some("/{thumb_name}/{image_stem}".to_string()),
+                size: (20, 20),
+                ..Default::default()
+            },
+        ];
+        let client = client_with_images(
+            settings,
+            &[
+                "images/penguin.png",
+                "images/puffin.png",
+                "thumbs/notes.txt",
+            ],
+        )
+        .await;
+        client
+            .create_thumbs_dir(Some("images"), "/thumbs", false)
+            .await
+            .unwrap();
+        client
+            .client
+            .delete(&Path::parse("images/puffin.png").unwrap())
+            .await
+            .unwrap();
+
+        let mut purged = client
+            .purge_orphans(Some("images"), "/thumbs")
+            .await
+            .unwrap();
+        purged.sort();
+        assert_eq!(
+            purged,
+            [
+                Path::parse("thumbs/puffin_mini.png").unwrap(),
+                Path::parse("thumbs/standard/puffin.png").unwrap()
+            ]
+        );
+        for path in [
+            "thumbs/penguin_mini.png",
+            "thumbs/standard/penguin.png",
+            "thumbs/notes.txt",
+        ] {
+            client
+                .client
+                .head(&Path::parse(path).unwrap())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn image_stem_of() {
+        let params = Params {
+            name: "mini".to_string(),
+            ..Default::default()
+        };
+        let stem = |thumb| ImageThumbs::<InMemory>::image_stem_of(thumb, &params, ImageFormat::Png);
+        assert_eq!(stem("penguin_mini"), Some("penguin"));
+        assert_eq!(stem("penguin_standard"), None);
+        assert_eq!(stem("_mini"), None);
+
+        let params = Params {
+            naming_pattern: Some("/{format}/{image_stem}_{width}x{height}".to_string()),
+            ..Params::new("mini", (400, 300), Mode::Crop, 80)
+        };
+        let stem = |thumb| ImageThumbs::<InMemory>::image_stem_of(thumb, &params, ImageFormat::Png);
+        assert_eq!(stem("png/penguin_400x300"), Some("penguin"));
+        assert_eq!(stem("jpg/penguin_400x300"), None);
+        assert_eq!(stem("png/penguin_40x30"), None);
+    }
+
+    #[tokio::test]
+    async fn delete_objects() {
+        let client = ImageThumbs::from_parts(InMemory::new(), Vec::new());
+        for path in ["penguin.png", "puffin.png", "seal.png"] {
+            client
+                .client
+                .put(&Path::parse(path).unwrap(), vec![1, 2, 3].into())
+                .await
+                .unwrap();
+        }
+
+        client.delete("penguin.png").await.unwrap();
+        client
+            .delete_all(&["puffin.png", "seal.png"])
+            .await
+            .unwrap();
+        let objects = ImageThumbs::<InMemory>::list_folder(&client.client, None)
+            .await
+            .unwrap();
+        assert!(objects.is_empty());
+    }
+}
@@ -0,0 +1,1117 @@
+use std::collections::HashSet;
+
+use futures::{future, stream, Stream, StreamExt};
+use glob::Pattern;
+use object_store::path::{Path, PathPart};
+use object_store::{Attribute, GetOptions, ObjectStore};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
+
+use crate::batch::BatchOutcome;
+use crate::checkpoint::Checkpoint;
+use crate::model::ThumbReport;
+use crate::timeout::{with_timeout, Timeouts};
+use crate::{Error, ImageThumbs, ThumbsResult};
+
+/// Name of the object metadata entry the entity tag of the source image a thumbnail was created
+/// from is stored in
+pub(crate) const SOURCE_ETAG_METADATA: &str = "source_etag";
+
+/// Options of [`ImageThumbs::create_thumbs_dir_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct DirOptions {
+    /// Overrides already existent thumbnails with the same name, instead of preserving them
+    pub force_override: bool,
+    /// Also processes the images in nested directories. Their thumbnails are stored in the same
+    /// nested directories below `dest_dir`, e.g., the thumbnails of `uploads/2024/05/penguin.jpg`
+    /// end up in `thumbs/2024/05` for the directory `uploads` and `dest_dir` `thumbs`, and in
+    /// `cdn/mini/2024/05` for presets with their own `dest_dir` `cdn/mini`.
+    pub recursive: bool,
+    /// Glob patterns, e.g., `*.jpg`, of which the file name must match at least one to be
+    /// processed. All files are processed if empty.
+    pub include: Vec<String>,
+    /// Glob patterns, e.g., `*_raw.*`, of file names that are skipped, even if they match
+    /// `include`.
+    pub exclude: Vec<String>,
+    /// Receives a [`DirProgress`] event for every step of the run. Events are dropped silently
+    /// once the receiver is closed.
+    pub progress: Option<UnboundedSender<DirProgress>>,
+    /// Continues with the next image if creating the thumbnails of an image fails, instead of
+    /// aborting the run. The failures are collected in the returned [`DirSummary`].
+    pub continue_on_error: bool,
+    /// Stops the run once cancelled, e.g., on shutdown. The image that is being processed at
+    /// that moment is finished first, so no thumbnails are left half-written.
+    pub cancel: Option<CancellationToken>,
+    /// Limits used instead of the ones set with [`ImageThumbs::with_timeouts`]
+    pub timeouts: Option<Timeouts>,
+    /// Number of images that are processed at the same time, e.g., `8` for large backfills.
+    /// `0` and `1` process one image after another.
+    pub concurrency: usize,
+    /// Skips images of which all thumbnails were created from the current version of the image,
+    /// even with `force_override`, without downloading them. Versions are compared by the entity
+    /// tag of the image that is stored with its thumbnails, so thumbnails in stores that cannot
+    /// keep metadata are always recreated.
+    pub skip_unchanged: bool,
+    /// Path of an object in the store, e.g., `jobs/backfill`, that the progress of the run is
+    /// saved to, so that an interrupted run continues after the last finished image instead of
+    /// starting over. The images are processed in the order of their paths, and the object is
+    /// deleted once all of them are finished.
+    ///
+    /// Only used by [`ImageThumbs::create_thumbs_dir_with_options`]; consumers of
+    /// [`ImageThumbs::create_thumbs_dir_stream`] receive every report and can keep track
+    /// themselves.
+    pub checkpoint: Option<String>,
+}
+
+/// Outcome of a directory run, see [`ImageThumbs::create_thumbs_dir_with_options`]. Counts the
+/// images of a [`BatchOutcome`] instead of reporting every one of them.
+#[derive(Debug, Default)]
+pub struct DirSummary {
+    /// Number of images of which the thumbnails were created
+    pub processed: usize,
+    /// Number of images that were skipped because all of their thumbnails already exist, or
+    /// because they are no images, e.g., videos
+    pub skipped: usize,
+    /// Images of which creating the thumbnails failed, only filled with `continue_on_error`
+    pub failed: Vec<(Path, Error)>,
+    /// Whether the run was stopped with [`DirOptions::cancel`] before all images were processed
+    pub cancelled: bool,
+}
+
+/// Progress of a directory run, see [`DirOptions::progress`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum DirProgress {
+    /// Number of images found in the directory that match the patterns
+    Discovered(usize),
+    /// All thumbnails of the image were created
+    Processed(Path),
+    /// The image was skipped because all of its thumbnails already exist, or because it is no
+    /// image
+    Skipped(Path),
+    /// Creating the thumbnails of the image failed with the given error message
+    Failed(Path, String),
+}
+
+impl DirOptions {
+    fn filter(&self) -> ThumbsResult<impl Fn(&Path) -> bool> {
+        let parse = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| Pattern::new(pattern))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let include = parse(&self.include)?;
+        let exclude = parse(&self.exclude)?;
+        Ok(move |path: &Path| {
+            let name = path.filename().unwrap_or_default();
+            (include.is_empty() || include.iter().any(|pattern| pattern.matches(name)))
+                && !exclude.iter().any(|pattern| pattern.matches(name))
+        })
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.is_cancelled())
+    }
+
+    fn report(&self, progress: DirProgress) {
+        if let Some(sender) = &self.progress {
+            let _ = sender.send(progress);
+        }
+    }
+}
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Like [`ImageThumbs::create_thumbs_dir`], but with additional options.
+    ///
+    /// # Arguments
+    /// * `directory` - directory to create thumbnails for.
+    ///
+    /// * `dest_dir` - directory to store all created thumbnails.
+    ///
+    /// * `options` - e.g., whether to override existent thumbnails or to include nested
+    ///   directories, or to only process images matching some patterns, see [`DirOptions`].
+    pub async fn create_thumbs_dir_with_options(
+        &self,
+        directory: Option<&str>,
+        dest_dir: &str,
+        options: &DirOptions,
+    ) -> ThumbsResult<DirSummary> {
+        let outcome = self
+            .process_dir(&self.client, directory, dest_dir, options)
+            .await?;
+        Ok(outcome.into())
+    }
+
+    /// Like [`ImageThumbs::create_thumbs_dir_with_options`], but always continues with the next
+    /// image after an error, regardless of [`DirOptions::continue_on_error`], and returns the
+    /// report of every image. Only fails if the directory cannot be listed or the checkpoint
+    /// cannot be saved.
+    ///
+    /// # Arguments
+    /// * `directory` - directory to create thumbnails for.
+    ///
+    /// * `dest_dir` - directory to store all created thumbnails.
+    ///
+    /// * `options` - see [`DirOptions`].
+    pub async fn create_thumbs_dir_batch(
+        &self,
+        directory: Option<&str>,
+        dest_dir: &str,
+        options: &DirOptions,
+    ) -> ThumbsResult<BatchOutcome> {
+        let options = DirOptions {
+            continue_on_error: true,
+            ..options.clone()
+        };
+        self.process_dir(&self.client, directory, dest_dir, &options)
+            .await
+    }
+
+    /// Like [`ImageThumbs::create_thumbs_dir_with_options`], but returns a stream with a
+    /// [`ThumbReport`] for every image instead of waiting for the whole directory.
+    ///
+    /// Images are only processed while the stream is polled. After an error, polling the stream
+    /// again continues with the next image. The stream ends early once [`DirOptions::cancel`] is
+    /// cancelled. With [`DirOptions::concurrency`], the reports arrive in the order the images
+    /// were finished.
+    ///
+    /// # Arguments
+    /// * `directory` - directory to create thumbnails for.
+    ///
+    /// * `dest_dir` - directory to store all created thumbnails.
+    ///
+    /// * `options` - see [`DirOptions`].
+    pub fn create_thumbs_dir_stream<'a>(
+        &'a self,
+        directory: Option<&'a str>,
+        dest_dir: &'a str,
+        options: &'a DirOptions,
+    ) -> impl Stream<Item = ThumbsResult<ThumbReport>> + 'a {
+        self.dir_stream(&self.client, directory, dest_dir, options)
+    }
+
+    pub(crate) async fn process_dir(
+        &self,
+        store: &dyn ObjectStore,
+        directory: Option<&str>,
+        dest_dir: &str,
+        options: &DirOptions,
+    ) -> ThumbsResult<BatchOutcome> {
+        let mut checkpoint = match &options.checkpoint {
+            Some(path) => Some(Checkpoint::load(self, store, path).await?),
+            None => None,
+        };
+        let mut reports = self.list_dir(store, directory, dest_dir, options).await?;
+        if let Some(checkpoint) = &mut checkpoint {
+            reports = checkpoint.remaining(reports);
+        }
+        let total = reports.len();
+        let mut results = stream::iter(reports)
+            .take_while(|_| future::ready(!options.is_cancelled()))
+            .map(|report| async move {
+                let image = report.image.clone();
+                (image, self.process_image(store, report, options).await)
+            })
+            .buffer_unordered(options.concurrency.max(1));
+
+        let mut outcome = BatchOutcome::default();
+        while let Some((image, result)) = results.next().await {
+            match result {
+                Err(e) if !options.continue_on_error => {
+                    if let Some(checkpoint) = &mut checkpoint {
+                        checkpoint.save().await?;
+                    }
+                    return Err(e);
+                }
+                result => outcome.push(image.clone(), result),
+            }
+            if let Some(checkpoint) = &mut checkpoint {
+                checkpoint.finish(image).await?;
+            }
+        }
+        outcome.cancelled = outcome.finished() < total;
+        match checkpoint {
+            Some(mut checkpoint) if outcome.cancelled => checkpoint.save().await?,
+            Some(checkpoint) => checkpoint.clear().await?,
+            None => {}
+        }
+        Ok(outcome)
+    }
+
+    fn dir_stream<'a>(
+        &'a self,
+        store: &'a dyn ObjectStore,
+        directory: Option<&'a str>,
+        dest_dir: &'a str,
+        options: &'a DirOptions,
+    ) -> impl Stream<Item = ThumbsResult<ThumbReport>> + 'a {
+        stream::once(self.list_dir(store, directory, dest_dir, options))
+            .map(move |listing| match listing {
+                Ok(reports) => stream::iter(reports)
+                    .take_while(move |_| future::ready(!options.is_cancelled()))
+                    .map(move |report| self.process_image(store, report, options))
+                    .buffer_unordered(options.concurrency.max(1))
+                    .left_stream(),
+                Err(e) => stream::once(future::ready(Err(e))).right_stream(),
+            })
+            .flatten()
+    }
+
+    /// Lists the images in the directory that match the patterns, marking those of which all
+    /// thumbnails already exist as skipped, unless `force_override` is set.
+    pub(crate) async fn list_dir(
+        &self,
+        store: &dyn ObjectStore,
+        directory: Option<&str>,
+        dest_dir: &str,
+        options: &DirOptions,
+    ) -> ThumbsResult<Vec<ThumbReport>> {
+        let prefix = match directory {
+            Some(p) => Some(Path::parse(p)?),
+            None => None,
+        };
+        // `None` for directories relative to the folder of each image
+        let dest_path = match Self::is_relative_dest_dir(dest_dir) {
+            true => None,
+            false => Some(Path::parse(dest_dir)?),
+        };
+        let filter = options.filter()?;
+
+        let source = self.source_or(store);
+        let images = if options.recursive {
+            Self::list_folder_recursive(source, prefix.as_ref()).await?
+        } else {
+            Self::list_folder(source, prefix.as_ref()).await?
+        };
+        let mut reports = images
+            .into_iter()
+            .filter(|image| filter(image))
+            .map(|image| {
+                let (dest_dir, nested) = match &dest_path {
+                    Some(dest_path) => {
+                        let nested = nested_folders(&image, prefix.as_ref());
+                        (dest_path.parts().chain(nested.parts()).collect(), nested)
+                    }
+                    None => (
+                        Self::resolve_dest_dir(dest_dir, image.as_ref())?,
+                        Path::default(),
+                    ),
+                };
+                Ok(ThumbReport {
+                    image,
+                    dest_dir,
+                    skipped: false,
+                    thumbs: Vec::new(),
+                    source_hash: None,
+                    nested,
+                })
+            })
+            .collect::<ThumbsResult<Vec<_>>>()?;
+        let dest_dirs: HashSet<Path> = match &dest_path {
+            Some(dest_path) => HashSet::from([dest_path.clone()]),
+            None => reports
+                .iter()
+                .map(|report| report.dest_dir.clone())
+                .collect(),
+        };
+        // existent thumbnails are no images to create thumbnails for, unless they are stored in
+        // the listed directory itself
+        let listed = prefix.clone().unwrap_or_default();
+        let mut excluded_dirs = HashSet::new();
+        for dest_dir in &dest_dirs {
+            excluded_dirs.extend(
+                self.thumb_dirs(dest_dir)?
+                    .into_iter()
+                    .filter(|dir| !listed.prefix_matches(dir)),
+            );
+        }
+        reports.retain(|report| {
+            !excluded_dirs
+                .iter()
+                .any(|dir| report.image.prefix_matches(dir))
+        });
+        options.report(DirProgress::Discovered(reports.len()));
+
+        if !options.force_override {
+            let mut thumb_dirs = HashSet::new();
+            let mut existent_thumbs = Vec::new();
+            for dest_dir in &dest_dirs {
+                for dir in self.thumb_dirs(dest_dir)? {
+                    if !thumb_dirs.insert(dir.clone()) {
+                        continue;
+                    }
+                    // relative directories hold the thumbnails of one folder only
+                    existent_thumbs.extend(if options.recursive && dest_path.is_some() {
+                        Self::list_folder_recursive(store, Some(&dir)).await?
+                    } else {
+                        Self::list_folder(store, Some(&dir)).await?
+                    });
+                }
+            }
+            let missing = self.filter_existent_thumbs(&reports, &existent_thumbs)?;
+            for report in &mut reports {
+                report.skipped = !missing.contains(&report.image);
+            }
+        }
+        Ok(reports)
+    }
+
+    /// Directories thumbnails are stored in, i.e., the given one and those of the presets with
+    /// their own directory
+    fn thumb_dirs(&self, dest_dir: &Path) -> ThumbsResult<Vec<Path>> {
+        let presets = self.presets();
+        let profiles = self.profiles.values().flat_map(|profile| profile.iter());
+        let mut dirs = vec![dest_dir.clone()];
+        for params in presets.iter().chain(profiles) {
+            let dir = params.thumb_dir(dest_dir, &Path::default())?;
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        Ok(dirs)
+    }
+
+    async fn process_image(
+        &self,
+        store: &dyn ObjectStore,
+        report: ThumbReport,
+        options: &DirOptions,
+    ) -> ThumbsResult<ThumbReport> {
+        if report.skipped || (options.skip_unchanged && self.is_unchanged(store, &report).await?) {
+            options.report(DirProgress::Skipped(report.image.clone()));
+            return Ok(ThumbReport {
+                skipped: true,
+                ..report
+            });
+        }
+
+        let result = self.sniff_and_create(store, &report, options).await;
+        match result {
+            Ok(report) if report.skipped => {
+                options.report(DirProgress::Skipped(report.image.clone()));
+                Ok(report)
+            }
+            Ok(report) => {
+                options.report(DirProgress::Processed(report.image.clone()));
+                Ok(report)
+            }
+            Err(e) => {
+                options.report(DirProgress::Failed(report.image, e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether all thumbnails of the image exist and were created from its current version,
+    /// compared by the entity tag of the image stored with every thumbnail.
+    pub(crate) async fn is_unchanged(
+        &self,
+        store: &dyn ObjectStore,
+        report: &ThumbReport,
+    ) -> ThumbsResult<bool> {
+        let source = self.source_or(store);
+        let Some(e_tag) = self.retrying(|| source.head(&report.image)).await?.e_tag else {
+            return Ok(false);
+        };
+        let Ok(thumbs) = self.thumb_paths(&report.image, &report.dest_dir, &report.nested) else {
+            return Ok(false); // not an image
+        };
+        for (_, path) in thumbs {
+            let head = || GetOptions {
+                head: true,
+                ..Default::default()
+            };
+            let result = match self.retrying(|| store.get_opts(&path, head())).await {
+                Ok(result) => result,
+                Err(object_store::Error::NotFound { .. }) => return Ok(false),
+                Err(err) => Err(err)?,
+            };
+            let stored = result
+                .attributes
+                .get(&Attribute::Metadata(SOURCE_ETAG_METADATA.into()));
+            if stored.is_none_or(|stored| stored.as_ref() != e_tag) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Creates the thumbnails of the image, after checking its first bytes so that large objects
+    /// which are no images, e.g., videos, are skipped without being downloaded entirely.
+    async fn sniff_and_create(
+        &self,
+        store: &dyn ObjectStore,
+        report: &ThumbReport,
+        options: &DirOptions,
+    ) -> ThumbsResult<ThumbReport> {
+        let timeouts = options.timeouts.unwrap_or(self.timeouts);
+        let sniffed = with_timeout(
+            timeouts.download,
+            "downloading",
+            self.sniff_format(self.source_or(store), &report.image),
+        )
+        .await;
+        match sniffed {
+            Err(e) if matches!(e.inner(), Error::NotSupported) => {
+                return Ok(ThumbReport {
+                    skipped: true,
+                    ..report.clone()
+                });
+            }
+            sniffed => sniffed?,
+        };
+        let (report, _) = self
+            .create_thumbs_in_with_report(
+                store,
+                report.image.as_ref(),
+                report.dest_dir.as_ref(),
+                &report.nested,
+                options.force_override,
+                options.timeouts,
+            )
+            .await?;
+        Ok(report)
+    }
+}
+
+/// Folders of the image below `prefix`, which are mirrored below the destination directories
+pub(crate) fn nested_folders(image: &Path, prefix: Option<&Path>) -> Path {
+    let parts: Vec<PathPart> = match prefix {
+        Some(prefix) => image
+            .prefix_match(prefix)
+            .map(|parts| parts.collect())
+            .unwrap_or_default(),
+        None => image.parts().collect(),
+    };
+    parts[..parts.len().saturating_sub(1)]
+        .iter()
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use image::ImageFormat;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    use super::nested_folders;
+    use crate::model::{Mode, Params};
+    use crate::test_utils::{client_with_images, png, put_all};
+    use crate::{CancellationToken, DirOptions, DirProgress, Error, ImageThumbs};
+
+    #[test]
+    fn mirror_nested_directories() {
+        let path = |path| Path::parse(path).unwrap();
+        assert_eq!(
+            nested_folders(&path("uploads/2024/05/penguin.jpg"), Some(&path("uploads"))),
+            path("2024/05")
+        );
+        assert_eq!(nested_folders(&path("penguin.jpg"), None), Path::default());
+    }
+
+    #[tokio::test]
+    async fn create_thumbs_recursive() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = client_with_images(
+            settings,
+            &["uploads/penguin.png", "uploads/2024/05/penguin.png"],
+        )
+        .await;
+
+        let options = DirOptions {
+            recursive: true,
+            ..Default::default()
+        };
+        client
+            .create_thumbs_dir_with_options(Some("uploads"), "thumbs", &options)
+            .await
+            .unwrap();
+        for path in ["thumbs/penguin_mini.png", "thumbs/2024/05/penguin_mini.png"] {
+            client
+                .client
+                .head(&Path::parse(path).unwrap())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn apply_profiles_by_prefix() {
+        let settings = vec![Params::new("standard", (20, 20), Mode::Fit, 80)];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings)
+            .with_profile(
+                "avatars",
+                vec![Params::new("small", (10, 10), Mode::Crop, 80)],
+            )
+            .with_prefix_profile("uploads/users/**", "avatars")
+            .unwrap();
+        let path = |path| Path::parse(path).unwrap();
+        let bytes = png();
+        put_all(
+            &client.client,
+            &["uploads/penguin.png", "uploads/users/alice/penguin.png"],
+            &bytes,
+        )
+        .await;
+
+        let options = DirOptions {
+            recursive: true,
+            ..Default::default()
+        };
+        let summary = client
+            .create_thumbs_dir_with_options(Some("uploads"), "thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!(summary.processed, 2);
+        let mut thumbs =
+            ImageThumbs::<InMemory>::list_folder_recursive(&client.client, Some(&path("thumbs")))
+                .await
+                .unwrap();
+        thumbs.sort();
+        assert_eq!(
+            thumbs,
+            [
+                path("thumbs/penguin_standard.png"),
+                path("thumbs/users/alice/penguin_small.png")
+            ]
+        );
+
+        let summary = client
+            .create_thumbs_dir_with_options(Some("uploads"), "thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!(summary.skipped, 2);
+    }
+
+    #[tokio::test]
+    async fn exclude_thumbs_inside_directory() {
+        let mut settings = vec![
+            Params::new("mini", (10, 10), Mode::Crop, 80),
+            Params::new("standard", (20, 20), Mode::Fit, 80),
+        ];
+        settings[1].dest_dir = Some("/cdn".to_string());
+        let client = client_with_images(settings, &["penguin.png", "albums/puffin.png"]).await;
+
+        let options = DirOptions {
+            recursive: true,
+            force_override: true,
+            ..Default::default()
+        };
+        for _ in 0..2 {
+            let summary = client
+                .create_thumbs_dir_with_options(None, "thumbs", &options)
+                .await
+                .unwrap();
+            assert_eq!((summary.processed, summary.skipped), (2, 0));
+        }
+        let mut stored = ImageThumbs::<InMemory>::list_folder_recursive(&client.client, None)
+            .await
+            .unwrap();
+        stored.sort();
+        assert_eq!(
+            stored,
+            [
+                "albums/puffin.png",
+                "cdn/albums/puffin_standard.png",
+                "cdn/penguin_standard.png",
+                "penguin.png",
+                "thumbs/albums/puffin_mini.png",
+                "thumbs/penguin_mini.png",
+            ]
+            .map(|path| Path::parse(path).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn store_in_preset_directory() {
+        let mut settings = vec![
+            Params::new("mini", (10, 10), Mode::Crop, 80),
+            Params::new("standard", (20, 20), Mode::Fit, 80),
+        ];
+        settings[0].dest_dir = Some("/cdn/mini".to_string());
+        let client = client_with_images(settings, &["uploads/penguin.png"]).await;
+
+        let summary = client
+            .create_thumbs_dir_with_options(Some("uploads"), "thumbs", &DirOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(summary.processed, 1);
+        for path in ["cdn/mini/penguin_mini.png", "thumbs/penguin_standard.png"] {
+            client
+                .client
+                .head(&Path::parse(path).unwrap())
+                .await
+                .unwrap();
+        }
+
+        let summary = client
+            .create_thumbs_dir_with_options(Some("uploads"), "thumbs", &DirOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(summary.skipped, 1);
+    }
+
+    #[tokio::test]
+    async fn mirror_folders_in_preset_directory() {
+        let mut settings = vec![Params::new("mini", (10, 10), Mode::Crop, 80)];
+        settings[0].dest_dir = Some("/cdn/mini".to_string());
+        let client =
+            client_with_images(settings, &["photos/a/penguin.png", "photos/b/penguin.png"]).await;
+
+        let options = DirOptions {
+            recursive: true,
+            ..Default::default()
+        };
+        let summary = client
+            .create_thumbs_dir_with_options(Some("photos"), "thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!(summary.processed, 2);
+        let summary = client
+            .create_thumbs_dir_with_options(Some("photos"), "thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!(summary.skipped, 2);
+
+        client
+            .create_thumbs_mirrored("photos/a/penguin.png", None, "thumbs", false)
+            .await
+            .unwrap();
+        for path in [
+            "cdn/mini/a/penguin_mini.png",
+            "cdn/mini/b/penguin_mini.png",
+            "cdn/mini/photos/a/penguin_mini.png",
+        ] {
+            client
+                .client
+                .head(&Path::parse(path).unwrap())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn store_next_to_images() {
+        let settings = vec![Params::new("mini", (10, 10), Mode::Crop, 80)];
+        let client = client_with_images(
+            settings,
+            &["albums/2024/penguin.png", "albums/2025/puffin.png"],
+        )
+        .await;
+
+        let options = DirOptions {
+            recursive: true,
+            ..Default::default()
+        };
+        let summary = client
+            .create_thumbs_dir_with_options(Some("albums"), "./thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!(summary.processed, 2);
+        for path in [
+            "albums/2024/thumbs/penguin_mini.png",
+            "albums/2025/thumbs/puffin_mini.png",
+        ] {
+            client
+                .client
+                .head(&Path::parse(path).unwrap())
+                .await
+                .unwrap();
+        }
+
+        let summary = client
+            .create_thumbs_dir_with_options(Some("albums"), "./thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!((summary.processed, summary.skipped), (0, 2));
+
+        client
+            .create_thumbs("albums/2024/penguin.png", ".", false)
+            .await
+            .unwrap();
+        client
+            .client
+            .head(&Path::parse("albums/2024/penguin_mini.png").unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn include_and_exclude_patterns() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client =
+            client_with_images(settings, &["penguin.png", "penguin_raw.png", "notes.txt"]).await;
+
+        let options = DirOptions {
+            include: vec!["*.png".to_string()],
+            exclude: vec!["*_raw.*".to_string()],
+            ..Default::default()
+        };
+        client
+            .create_thumbs_dir_with_options(None, "thumbs", &options)
+            .await
+            .unwrap();
+        let thumbs = client
+            .client
+            .list_with_delimiter(Some(&Path::parse("thumbs").unwrap()))
+            .await
+            .unwrap()
+            .objects;
+        assert_eq!(thumbs.len(), 1);
+        assert_eq!(thumbs[0].location.as_ref(), "thumbs/penguin_mini.png");
+
+        let options = DirOptions {
+            include: vec!["[".to_string()],
+            ..Default::default()
+        };
+        let result = client
+            .create_thumbs_dir_with_options(None, "thumbs", &options)
+            .await;
+        assert!(matches!(result, Err(Error::Pattern(_))));
+    }
+
+    #[tokio::test]
+    async fn report_progress() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client =
+            client_with_images(settings, &["images/penguin.png", "images/puffin.png"]).await;
+        let path = |path| Path::parse(path).unwrap();
+        let bytes = png();
+        client
+            .client
+            .put(&path("thumbs/penguin_mini.png"), Vec::new().into())
+            .await
+            .unwrap();
+        client
+            .client
+            .put(&path("images/unreadable.png"), bytes[..64].to_vec().into())
+            .await
+            .unwrap();
+
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let options = DirOptions {
+            progress: Some(sender),
+            ..Default::default()
+        };
+        let result = client
+            .create_thumbs_dir_with_options(Some("images"), "thumbs", &options)
+            .await;
+        assert!(result.is_err());
+        drop(options);
+
+        let mut events = Vec::new();
+        while let Some(event) = receiver.recv().await {
+            events.push(event);
+        }
+        assert_eq!(events[0], DirProgress::Discovered(3));
+        assert!(events.contains(&DirProgress::Skipped(path("images/penguin.png"))));
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, DirProgress::Failed(image, _) if image == &path("images/unreadable.png"))));
+    }
+
+    #[tokio::test]
+    async fn stream_reports() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = png();
+        let path = |path| Path::parse(path).unwrap();
+        client
+            .client
+            .put(&path("images/corrupt.png"), bytes[..64].to_vec().into())
+            .await
+            .unwrap();
+        client
+            .client
+            .put(&path("images/penguin.png"), bytes.into())
+            .await
+            .unwrap();
+
+        let options = DirOptions::default();
+        let results: Vec<_> = client
+            .create_thumbs_dir_stream(Some("images"), "thumbs", &options)
+            .collect()
+            .await;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_err());
+        let report = results[1].as_ref().unwrap();
+        assert_eq!(report.image, path("images/penguin.png"));
+        assert_eq!(report.dest_dir, path("thumbs"));
+        assert!(!report.skipped);
+
+        let reports: Vec<_> = client
+            .create_thumbs_dir_stream(Some("images"), "thumbs", &options)
+            .filter_map(|result| async { result.ok() })
+            .collect()
+            .await;
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].skipped);
+    }
+
+    #[tokio::test]
+    async fn continue_on_error() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = png();
+        let path = |path| Path::parse(path).unwrap();
+        client
+            .client
+            .put(&path("images/corrupt.png"), bytes[..64].to_vec().into())
+            .await
+            .unwrap();
+        client
+            .client
+            .put(&path("images/penguin.png"), bytes.into())
+            .await
+            .unwrap();
+
+        let result = client
+            .create_thumbs_dir_with_options(Some("images"), "thumbs", &DirOptions::default())
+            .await;
+        assert!(result.is_err());
+
+        let options = DirOptions {
+            continue_on_error: true,
+            ..Default::default()
+        };
+        let summary = client
+            .create_thumbs_dir_with_options(Some("images"), "thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!(summary.processed, 1);
+        assert_eq!(summary.skipped, 0);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].0, path("images/corrupt.png"));
+    }
+
+    #[tokio::test]
+    async fn cancel_run() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client =
+            client_with_images(settings, &["images/penguin.png", "images/puffin.png"]).await;
+
+        let cancel = CancellationToken::new();
+        let options = DirOptions {
+            cancel: Some(cancel.clone()),
+            ..Default::default()
+        };
+        let mut reports =
+            Box::pin(client.create_thumbs_dir_stream(Some("images"), "thumbs", &options));
+        let report = reports.next().await.unwrap().unwrap();
+        assert_eq!(report.image, Path::parse("images/penguin.png").unwrap());
+        cancel.cancel();
+        assert!(reports.next().await.is_none());
+        drop(reports);
+
+        let summary = client
+            .create_thumbs_dir_with_options(Some("images"), "thumbs", &options)
+            .await
+            .unwrap();
+        assert!(summary.cancelled);
+        assert_eq!(summary.processed, 0);
+    }
+
+    #[tokio::test]
+    async fn concurrent_run() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = png();
+        for i in 0..6 {
+            client
+                .client
+                .put(
+                    &Path::parse(format!("images/penguin{i}.png")).unwrap(),
+                    bytes.clone().into(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let options = DirOptions {
+            concurrency: 4,
+            ..Default::default()
+        };
+        let summary = client
+            .create_thumbs_dir_with_options(Some("images"), "thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!(summary.processed, 6);
+        assert!(!summary.cancelled);
+    }
+
+    #[tokio::test]
+    async fn sniff_before_download() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = client_with_images(settings, &["media/penguin.png"]).await;
+        let path = |path| Path::parse(path).unwrap();
+        client
+            .client
+            .put(&path("media/movie.mp4"), vec![0; 1 << 20].into())
+            .await
+            .unwrap();
+
+        let format = client
+            .sniff_format(&client.client, &path("media/penguin.png"))
+            .await
+            .unwrap();
+        assert_eq!(format, ImageFormat::Png);
+        let result = client
+            .sniff_format(&client.client, &path("media/movie.mp4"))
+            .await;
+        assert!(matches!(result.unwrap_err().inner(), Error::NotSupported));
+
+        let options = DirOptions {
+            continue_on_error: true,
+            ..Default::default()
+        };
+        let summary = client
+            .create_thumbs_dir_with_options(Some("media"), "thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!(summary.processed, 1);
+        assert_eq!(summary.skipped, 1);
+        assert!(summary.failed.is_empty());
+
+        // no images do not stop the run without `continue_on_error` either
+        let summary = client
+            .create_thumbs_dir_with_options(Some("media"), "thumbs", &DirOptions::default())
+            .await
+            .unwrap();
+        assert_eq!((summary.processed, summary.skipped), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn skip_unchanged_images() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = png();
+        let image = Path::parse("images/penguin.png").unwrap();
+        client
+            .client
+            .put(&image, bytes.clone().into())
+            .await
+            .unwrap();
+
+        let options = DirOptions {
+            force_override: true,
+            skip_unchanged: true,
+            ..Default::default()
+        };
+        for (processed, skipped) in [(1, 0), (0, 1)] {
+            let summary = client
+                .create_thumbs_dir_with_options(Some("images"), "thumbs", &options)
+                .await
+                .unwrap();
+            assert_eq!((summary.processed, summary.skipped), (processed, skipped));
+        }
+
+        client
+            .client
+            .put(&image, bytes.clone().into())
+            .await
+            .unwrap();
+        let summary = client
+            .create_thumbs_dir_with_options(Some("images"), "thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!((summary.processed, summary.skipped), (1, 0));
+
+        // thumbnails created on demand keep track of the image version as well
+        put_all(&client.client, &["images/puffin.png"], &bytes).await;
+        client
+            .get_or_create_thumb("images/puffin.png", "mini", "thumbs")
+            .await
+            .unwrap();
+        let summary = client
+            .create_thumbs_dir_with_options(Some("images"), "thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!((summary.processed, summary.skipped), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn resume_from_checkpoint() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = png();
+        let path = |path| Path::parse(path).unwrap();
+        for image in ["images/a.png", "images/b.png", "images/c.png"] {
+            client
+                .client
+                .put(&path(image), bytes.clone().into())
+                .await
+                .unwrap();
+        }
+        client
+            .client
+            .put(&path("jobs/backfill"), "images/b.png".into())
+            .await
+            .unwrap();
+
+        let options = DirOptions {
+            checkpoint: Some("jobs/backfill".to_string()),
+            ..Default::default()
+        };
+        let summary = client
+            .create_thumbs_dir_with_options(Some("images"), "thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!(summary.processed, 1);
+        client
+            .client
+            .head(&path("thumbs/c_mini.png"))
+            .await
+            .unwrap();
+        assert!(client
+            .client
+            .head(&path("thumbs/a_mini.png"))
+            .await
+            .is_err());
+        assert!(client.client.head(&path("jobs/backfill")).await.is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use config::{File, FileFormat};
+use object_store::ObjectStore;
+
+use crate::model::Params;
+use crate::{load_settings, ImageThumbs, ThumbsResult};
+
+/// [`ImageThumbs`] over a store that is chosen at runtime, e.g., based on configuration.
+pub type DynImageThumbs = ImageThumbs<Arc<dyn ObjectStore>>;
+
+impl ImageThumbs<Arc<dyn ObjectStore>> {
+    /// Creates new ImageThumbs instance for an arbitrary, already configured store.
+    ///
+    /// Reads the config file to know which thumbnails to create, see [`ImageThumbs::new`].
+    ///
+    /// # Arguments
+    /// * `store` - Store to read images from and write thumbnails to
+    ///
+    /// * `config` - Path to the config file from the crate root (the extension may be omitted)
+    pub async fn from_store(store: Arc<dyn ObjectStore>, config: &str) -> ThumbsResult<Self> {
+        Self::from_store_with_settings(store, Self::settings(config)?).await
+    }
+
+    pub async fn from_store_with_settings(
+        store: Arc<dyn ObjectStore>,
+        settings: Vec<Params>,
+    ) -> ThumbsResult<Self> {
+        Ok(Self::from_parts(store, settings))
+    }
+
+    /// Parses the settings from a string instead of a file, e.g., a config embedded with
+    /// `include_str!` or taken from the configuration system of the service. The result can be
+    /// passed to every constructor taking settings, e.g., [`ImageThumbs::new_with_settings`].
+    ///
+    /// As it is only defined for this store type, it can be called without naming the store:
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let settings = image_thumbs::ImageThumbs::settings_from_str(
+    ///     "thumbs: [{ name: mini, quality: 80, size: [40, 40], mode: crop }]",
+    /// )
+    /// .unwrap();
+    /// let thumbs = image_thumbs::ImageThumbs::new_local_with_settings("images", settings)
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    ///
+    /// # Arguments
+    /// * `config` - YAML or JSON with the structure of `examples/image_thumbs.yaml`, see
+    ///   [`ImageThumbs::new`].
+    pub fn settings_from_str(config: &str) -> ThumbsResult<Vec<Params>> {
+        load_settings(File::from_str(config, FileFormat::Yaml))
+    }
+}
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Erases the store type, so that instances for different providers can be used
+    /// interchangeably.
+    pub fn into_dyn(self) -> DynImageThumbs {
+        ImageThumbs {
+            client: Arc::new(self.client),
+            settings: self.settings,
+            profiles: self.profiles,
+            prefix_profiles: self.prefix_profiles,
+            source: self.source,
+            replicas: self.replicas,
+            replication_summary: self.replication_summary,
+            replication_pending: self.replication_pending,
+            blurhash: self.blurhash,
+            thumbhash: self.thumbhash,
+            timeouts: self.timeouts,
+            multipart_threshold: self.multipart_threshold,
+            retries: self.retries,
+            limiter: self.limiter,
+            #[cfg(feature = "faces")]
+            face_model: self.face_model,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use image::ImageFormat;
+    use object_store::local::LocalFileSystem;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    use crate::model::Params;
+    use crate::test_utils::png;
+    use crate::{DynImageThumbs, ImageThumbs};
+
+    #[tokio::test]
+    async fn choose_store_at_runtime() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let memory = ImageThumbs::from_parts(InMemory::new(), settings.clone()).into_dyn();
+        let local = ImageThumbs::from_parts(LocalFileSystem::new(), settings).into_dyn();
+        let clients: Vec<DynImageThumbs> = vec![memory, local];
+        assert_eq!(clients.len(), 2);
+
+        let store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let client = ImageThumbs::from_store_with_settings(
+            store.clone(),
+            vec![Params {
+                name: "mini".to_string(),
+                size: (10, 10),
+                ..Default::default()
+            }],
+        )
+        .await
+        .unwrap();
+        let bytes = png();
+        client
+            .create_thumbs_from_bytes(
+                bytes,
+                "/thumbs",
+                "penguin",
+                ImageFormat::Png,
+                false,
+                (0.5, 0.5),
+            )
+            .await
+            .unwrap();
+        store
+            .head(&Path::parse("thumbs/penguin_mini.png").unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn settings_from_str() {
+        let settings = ImageThumbs::settings_from_str(
+            "thumbs:\n  - name: mini\n    quality: 80\n    size: [40, 40]\n    mode: crop",
+        )
+        .unwrap();
+        assert_eq!(settings[0].name, "mini");
+        let settings = ImageThumbs::settings_from_str(
+            r#"{"thumbs": [{"name": "mini", "quality": 80, "size": [40, 20], "mode": "fit"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(settings[0].size, (40, 20));
+
+        let client = ImageThumbs::from_store_with_settings(Arc::new(InMemory::new()), settings)
+            .await
+            .unwrap();
+        assert_eq!(client.presets().len(), 1);
+        assert!(ImageThumbs::settings_from_str("thumbs: [{ name: mini }]").is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use config::builder::DefaultState;
+use config::{ConfigBuilder, Environment, Source, Value, ValueKind};
+
+use crate::ThumbsResult;
+
+/// Prefix of the environment variables that override fields of the presets
+const ENV_PREFIX: &str = "THUMBS";
+
+/// Environment variables like `THUMBS__STANDARD__QUALITY=70`, i.e., the prefix, the name of the
+/// preset, and the field, separated by double underscores.
+pub(crate) fn environment() -> Environment {
+    Environment::with_prefix(ENV_PREFIX)
+        .separator("__")
+        .try_parsing(true)
+}
+
+/// Layers the fields set in the environment over the fields of the given presets. Comma separated
+/// values, e.g., `640,480` for the size, are read as lists.
+pub(crate) fn env_overrides(
+    mut builder: ConfigBuilder<DefaultState>,
+    presets: Vec<Value>,
+    environment: &Environment,
+) -> ThumbsResult<ConfigBuilder<DefaultState>> {
+    let overrides = environment.collect()?;
+    for (index, preset) in presets.into_iter().enumerate() {
+        let Some(name) = preset
+            .into_table()?
+            .remove("name")
+            .and_then(|name| name.into_string().ok())
+        else {
+            continue;
+        };
+        for (key, value) in &overrides {
+            let Some((preset, field)) = key.split_once('.') else {
+                continue;
+            };
+            if preset == name.to_lowercase() {
+                builder = builder.set_override(format!("thumbs[{index}].{field}"), list(value))?;
+            }
+        }
+    }
+    Ok(builder)
+}
+
+fn list(value: &Value) -> Value {
+    match &value.kind {
+        ValueKind::String(value) if value.contains(',') => {
+            let items: Vec<Value> = value
+                .split(',')
+                .map(|item| Value::from(item.trim()))
+                .collect();
+            Value::from(items)
+        }
+        _ => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{Config, File, FileFormat, Map};
+
+    use super::{env_overrides, environment};
+    use crate::model::{Mode, Params};
+
+    #[test]
+    fn override_preset_fields() {
+        let config = "thumbs:
+  - { name: standard, quality: 80, size: [640, 480], mode: fit }
+  - { name: mini, quality: 80, size: [40, 40], mode: crop }";
+        let variables = [
+            ("THUMBS__STANDARD__QUALITY", "70"),
+            ("THUMBS__STANDARD__SIZE", "800, 600"),
+            ("THUMBS__MINI__MODE", "fit"),
+            ("OTHER__MINI__QUALITY", "10"),
+        ];
+        let environment = environment().source(Some(Map::from_iter(
+            variables.map(|(key, value)| (key.to_string(), value.to_string())),
+        )));
+
+        let builder = Config::builder().add_source(File::from_str(config, FileFormat::Yaml));
+        let presets = builder
+            .clone()
+            .build()
+            .unwrap()
+            .get_array("thumbs")
+            .unwrap();
+        let settings: Vec<Params> = env_overrides(builder, presets, &environment)
+            .unwrap()
+            .build()
+            .unwrap()
+            .get("thumbs")
+            .unwrap();
+        assert_eq!(settings[0].quality, 70);
+        assert_eq!(settings[0].size, (800, 600));
+        assert_eq!(settings[1].quality, 80);
+        assert!(matches!(settings[1].mode, Mode::Fit));
+    }
+}
@@ -1,4 +1,4 @@
-use image::ImageError;
+use image::{ImageError, ImageFormat};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -9,12 +9,92 @@ pub enum Error {
     Path(#[from] object_store::path::Error),
     #[error("Configuration error: {0}")]
     Config(#[from] config::ConfigError),
+    #[error("Missing configuration: {0}")]
+    MissingConfig(&'static str),
+    #[error("Invalid configuration: {}", .0.join("; "))]
+    InvalidSettings(Vec<String>),
     #[error("Image error: {0}")]
     Image(ImageError),
     #[error("Image format not supported")]
     NotSupported,
+    #[error("Corrupt {format:?} image of {length} bytes (truncated: {truncated}): {source}")]
+    CorruptImage {
+        /// Format the image was decoded as
+        format: ImageFormat,
+        /// Number of bytes of the image
+        length: usize,
+        /// Whether the data ends before the end marker of the format, e.g., after an
+        /// interrupted upload, rather than containing garbage
+        truncated: bool,
+        source: ImageError,
+    },
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
     #[error("Utf-8 error")]
     Utf,
+    #[error("Unknown thumbnail preset: {0}")]
+    UnknownPreset(String),
+    #[error("Unknown profile: {0}")]
+    UnknownProfile(String),
+    #[error("Thumbnail paths of preset {0} depend on the contents of the image")]
+    ContentDependentPath(String),
+    #[error("Source image not found: {0}")]
+    SourceNotFound(String),
+    #[error("Failed {operation} {path}: {source}")]
+    Processing {
+        /// Object the operation failed for, e.g., the image of a batch that cannot be decoded
+        path: String,
+        /// What was being done, e.g., `downloading` or `decoding`
+        operation: &'static str,
+        source: Box<Error>,
+    },
+    #[error("Invalid file pattern: {0}")]
+    Pattern(#[from] glob::PatternError),
+    #[error("Timeout while {0}")]
+    Timeout(&'static str),
+    #[error("Crop rectangle lies outside of the image")]
+    InvalidCrop,
+    #[cfg(feature = "caption")]
+    #[error("Font error: {0}")]
+    Font(String),
+    #[cfg(feature = "faces")]
+    #[error("Face detection model error: {0}")]
+    FaceModel(String),
+    #[cfg(feature = "url")]
+    #[error("Download error: {0}")]
+    Download(#[from] reqwest::Error),
+    #[cfg(feature = "fast_resize")]
+    #[error("Resize error: {0}")]
+    Resize(String),
+}
+
+impl Error {
+    /// The error without the context of [`Error::Processing`], e.g., to match on the kind of
+    /// error.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Self::Processing { source, .. } => source.inner(),
+            _ => self,
+        }
+    }
+
+    /// Adds the path of the object and the operation to storage and image errors, so that the
+    /// broken object of a batch can be told apart. Other errors, e.g., unknown presets, are not
+    /// specific to one object and are returned as they are.
+    pub(crate) fn context(self, operation: &'static str, path: impl ToString) -> Self {
+        match self {
+            Self::Storage(_)
+            | Self::Image(_)
+            | Self::NotSupported
+            | Self::CorruptImage { .. }
+            | Self::Io(_) => Self::Processing {
+                path: path.to_string(),
+                operation,
+                source: Box::new(self),
+            },
+            _ => self,
+        }
+    }
 }
 
 impl From<ImageError> for Error {
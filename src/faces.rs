@@ -0,0 +1,74 @@
+use std::fmt::{Debug, Formatter};
+use std::io::Cursor;
+
+use image::DynamicImage;
+use object_store::ObjectStore;
+use rustface::{ImageData, Model};
+
+use crate::{Error, ImageThumbs, ThumbsResult};
+
+/// Smallest face (in pixels) that is detected
+const MIN_FACE_SIZE: u32 = 20;
+
+/// Face detection model used to keep faces inside the crop window
+#[derive(Clone)]
+pub(crate) struct FaceModel(Model);
+
+impl Debug for FaceModel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("FaceModel")
+    }
+}
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Enables face-aware cropping: in `crop` mode, the crop window is centered on the detected
+    /// faces for presets without a `center`. A center given for the call, e.g., with
+    /// [`ImageThumbs::create_thumbs_man_center`], is used instead.
+    ///
+    /// # Arguments
+    /// * `model` - Path to a SeetaFace detection model, e.g., `seeta_fd_frontal_v1.0.bin` as
+    ///   distributed with [rustface](https://github.com/atomashpolskiy/rustface).
+    pub fn with_face_model(mut self, model: &str) -> ThumbsResult<Self> {
+        let bytes = std::fs::read(model).map_err(|e| Error::FaceModel(e.to_string()))?;
+        let model = rustface::read_model(Cursor::new(bytes))
+            .map_err(|e| Error::FaceModel(e.to_string()))?;
+        self.face_model = Some(FaceModel(model));
+        Ok(self)
+    }
+}
+
+impl FaceModel {
+    /// Returns the center of the area covering all detected faces in percent (i.e., between 0 and
+    /// 1), or `None` if no face was found.
+    pub(crate) fn face_center(&self, image: &DynamicImage) -> Option<(f32, f32)> {
+        let FaceModel(model) = self;
+        let mut detector = rustface::create_detector_with_model(model.clone());
+        detector.set_min_face_size(MIN_FACE_SIZE);
+        detector.set_score_thresh(2.0);
+        detector.set_pyramid_scale_factor(0.8);
+        detector.set_slide_window_step(4, 4);
+
+        let gray = image.to_luma8();
+        let faces = detector.detect(&ImageData::new(gray.as_raw(), gray.width(), gray.height()));
+        if faces.is_empty() {
+            return None;
+        }
+
+        let (left, top, right, bottom) = faces.iter().map(|face| face.bbox()).fold(
+            (i32::MAX, i32::MAX, i32::MIN, i32::MIN),
+            |(left, top, right, bottom), bbox| {
+                (
+                    left.min(bbox.x()),
+                    top.min(bbox.y()),
+                    right.max(bbox.x() + bbox.width() as i32),
+                    bottom.max(bbox.y() + bbox.height() as i32),
+                )
+            },
+        );
+
+        Some((
+            ((left + right) as f32 / 2. / gray.width() as f32).clamp(0., 1.),
+            ((top + bottom) as f32 / 2. / gray.height() as f32).clamp(0., 1.),
+        ))
+    }
+}
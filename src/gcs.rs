@@ -1,14 +1,37 @@
+use http::{HeaderMap, HeaderName, HeaderValue};
 use object_store::gcp::{GoogleCloudStorage, GoogleCloudStorageBuilder};
+use object_store::ClientOptions;
 
 use crate::model::Params;
-use crate::{ImageThumbs, ThumbsResult};
+use crate::{Error, ImageThumbs, ThumbsResult};
+
+/// Environment variables with the storage class, e.g., `STANDARD` or `NEARLINE`, and the
+/// predefined ACL, e.g., `public-read`, of all uploaded objects, and the headers they are sent in
+const OBJECT_HEADERS: [(&str, &str); 2] = [
+    ("GOOGLE_STORAGE_CLASS", "x-goog-storage-class"),
+    ("GOOGLE_PREDEFINED_ACL", "x-goog-acl"),
+];
+
+/// Google service account used to connect to GCS.
+#[derive(Debug, Clone)]
+pub enum ServiceAccount {
+    /// JSON serialized service account key
+    Key(String),
+    /// Location of a service account file
+    Path(String),
+}
 
 impl ImageThumbs<GoogleCloudStorage> {
     /// Creates new ImageThumbs instance connected to Google Cloud Storage using the environment
     /// variables `GOOGLE_BUCKET` and `GOOGLE_SERVICE_ACCOUNT_KEY` to connect to GCS.
     /// The later should be in the JSON format.
     ///
-    /// Reads the config YAML file to know which thumbnails to create
+    /// All uploaded thumbnails get the storage class in `GOOGLE_STORAGE_CLASS` (e.g., `STANDARD`)
+    /// and the predefined ACL in `GOOGLE_PREDEFINED_ACL` (e.g., `public-read`), if set, instead of
+    /// the defaults of the bucket. Originals in another storage class can be read from a separate
+    /// instance added with [`ImageThumbs::with_source`].
+    ///
+    /// Reads the config file to know which thumbnails to create
     ///
     /// The config file must look like the example in `examples/image_thumbs.yaml`:
     /// ```yaml
@@ -16,23 +39,122 @@ impl ImageThumbs<GoogleCloudStorage> {
     /// ```
     ///
     /// # Arguments
-    /// * `config` - Path to the config file from the crate root (`.yaml` may be omitted)
+    /// * `config` - Path to the config file from the crate root (the extension may be omitted)
     pub async fn new(config: &str) -> ThumbsResult<Self> {
         let client = GoogleCloudStorageBuilder::from_env()
-            .with_client_options(Self::client_options())
+            .with_client_options(Self::gcs_client_options()?)
             .build()?;
 
-        Ok(Self {
-            client,
-            settings: Self::settings(config)?,
-        })
+        Ok(Self::from_parts(client, Self::settings(config)?))
     }
 
     pub async fn new_with_settings(settings: Vec<Params>) -> ThumbsResult<Self> {
         let client = GoogleCloudStorageBuilder::from_env()
-            .with_client_options(Self::client_options())
+            .with_client_options(Self::gcs_client_options()?)
             .build()?;
 
-        Ok(Self { client, settings })
+        Ok(Self::from_parts(client, settings))
+    }
+
+    /// Creates new ImageThumbs instance connected to the given Google Cloud Storage bucket using
+    /// the given service account, instead of reading them from environment variables.
+    ///
+    /// Reads the config file to know which thumbnails to create, and the storage class and ACL of
+    /// the thumbnails from the environment, see [`ImageThumbs::new`].
+    ///
+    /// # Arguments
+    /// * `bucket` - Name of the bucket
+    ///
+    /// * `service_account` - Key or key file of the service account
+    ///
+    /// * `config` - Path to the config file from the crate root (the extension may be omitted)
+    pub async fn new_with_credentials(
+        bucket: &str,
+        service_account: ServiceAccount,
+        config: &str,
+    ) -> ThumbsResult<Self> {
+        Self::new_with_credentials_and_settings(bucket, service_account, Self::settings(config)?)
+            .await
+    }
+
+    pub async fn new_with_credentials_and_settings(
+        bucket: &str,
+        service_account: ServiceAccount,
+        settings: Vec<Params>,
+    ) -> ThumbsResult<Self> {
+        let builder = GoogleCloudStorageBuilder::new().with_bucket_name(bucket);
+        let builder = match service_account {
+            ServiceAccount::Key(key) => builder.with_service_account_key(key),
+            ServiceAccount::Path(path) => builder.with_service_account_path(path),
+        };
+        let client = builder
+            .with_client_options(Self::gcs_client_options()?)
+            .build()?;
+
+        Ok(Self::from_parts(client, settings))
+    }
+
+    /// Client options that send the storage class and ACL from the environment with every request,
+    /// which the XML API of GCS applies to uploaded objects and ignores otherwise.
+    fn gcs_client_options() -> ThumbsResult<ClientOptions> {
+        let variables = OBJECT_HEADERS.map(|(variable, _)| std::env::var(variable).ok());
+        Ok(Self::client_options().with_default_headers(object_headers(variables)?))
+    }
+}
+
+/// Headers with the given values of the variables in [`OBJECT_HEADERS`].
+fn object_headers(values: [Option<String>; 2]) -> ThumbsResult<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    let mut problems = Vec::new();
+    for ((variable, header), value) in OBJECT_HEADERS.into_iter().zip(values) {
+        let Some(value) = value.filter(|value| !value.is_empty()) else {
+            continue;
+        };
+        match HeaderValue::from_str(&value) {
+            Ok(value) => {
+                headers.insert(HeaderName::from_static(header), value);
+            }
+            Err(e) => problems.push(format!("{variable}: {e}")),
+        }
+    }
+    if problems.is_empty() {
+        Ok(headers)
+    } else {
+        Err(Error::InvalidSettings(problems))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::object_headers;
+    use crate::{Error, ImageThumbs, ServiceAccount};
+
+    #[test]
+    fn storage_class_and_acl_headers() {
+        let headers = object_headers([
+            Some("STANDARD".to_string()),
+            Some("public-read".to_string()),
+        ])
+        .unwrap();
+        assert_eq!(headers["x-goog-storage-class"], "STANDARD");
+        assert_eq!(headers["x-goog-acl"], "public-read");
+        assert!(object_headers([Some(String::new()), None])
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            object_headers([Some("STANDARD\n".to_string()), None]),
+            Err(Error::InvalidSettings(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn invalid_service_account_key() {
+        let result = ImageThumbs::new_with_credentials_and_settings(
+            "bucket",
+            ServiceAccount::Key("no json".to_string()),
+            Vec::new(),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::Storage(_))));
     }
 }
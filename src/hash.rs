@@ -0,0 +1,283 @@
+use std::f32::consts::PI;
+
+use image::DynamicImage;
+use object_store::ObjectStore;
+
+use crate::ImageThumbs;
+
+/// Size of the longer edge of the downscaled image the hashes are computed on. Placeholder hashes
+/// only encode the rough colors, so computing them on the full image would only waste time.
+const HASH_IMAGE_SIZE: u32 = 64;
+
+/// ThumbHash is specified for images of at most 100x100 pixels
+const THUMBHASH_IMAGE_SIZE: u32 = 100;
+
+/// Name of the object metadata entry the BlurHash of the source image is stored in
+pub(crate) const BLURHASH_METADATA: &str = "blurhash";
+/// Name of the object metadata entry the base64 encoded ThumbHash of the source image is stored in
+pub(crate) const THUMBHASH_METADATA: &str = "thumbhash";
+
+/// Placeholder hashes of a processed image
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImageHashes {
+    /// [BlurHash](https://blurha.sh), if enabled with [`ImageThumbs::with_blurhash`]
+    pub blurhash: Option<String>,
+    /// [ThumbHash](https://evanw.github.io/thumbhash/), if enabled with
+    /// [`ImageThumbs::with_thumbhash`]
+    pub thumbhash: Option<Vec<u8>>,
+    /// Hash of the contents of the source image, if a naming pattern contains `{source_hash}`
+    pub source_hash: Option<String>,
+}
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Computes a [BlurHash](https://blurha.sh) of every processed image and stores it as
+    /// `blurhash` metadata on each of its thumbnails.
+    ///
+    /// # Arguments
+    /// * `components_x`, `components_y` - number of horizontal and vertical components of the
+    ///   hash; more components preserve more detail. Values are clamped to the supported range of
+    ///   1 to 9, `(4, 3)` is a common choice.
+    pub fn with_blurhash(mut self, components_x: u32, components_y: u32) -> Self {
+        self.blurhash = Some((components_x.clamp(1, 9), components_y.clamp(1, 9)));
+        self
+    }
+
+    /// Computes a [ThumbHash](https://evanw.github.io/thumbhash/) of every processed image and
+    /// stores it base64 encoded as `thumbhash` metadata on each of its thumbnails.
+    ///
+    /// Unlike BlurHash, ThumbHash preserves the aspect ratio and transparency of the image.
+    pub fn with_thumbhash(mut self) -> Self {
+        self.thumbhash = true;
+        self
+    }
+}
+
+/// Computes the placeholder hashes that are enabled, see [`ImageThumbs::with_blurhash`] and
+/// [`ImageThumbs::with_thumbhash`].
+pub(crate) fn image_hashes(
+    image: &DynamicImage,
+    blurhash_components: Option<(u32, u32)>,
+    with_thumbhash: bool,
+) -> ImageHashes {
+    ImageHashes {
+        blurhash: blurhash_components.and_then(|components| blurhash(image, components)),
+        thumbhash: with_thumbhash.then(|| thumbhash(image)),
+        source_hash: None,
+    }
+}
+
+/// Hex encoded hash of the bytes that stays the same across versions and platforms, unlike the
+/// hashers of the standard library
+pub(crate) fn stable_hash(bytes: &[u8]) -> String {
+    // FNV-1a
+    let hash = bytes.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+fn blurhash(image: &DynamicImage, components: (u32, u32)) -> Option<String> {
+    let small = image
+        .thumbnail(HASH_IMAGE_SIZE, HASH_IMAGE_SIZE)
+        .into_rgba8();
+    blurhash::encode(
+        components.0,
+        components.1,
+        small.width(),
+        small.height(),
+        small.as_raw(),
+    )
+    .ok()
+}
+
+/// Port of the reference implementation of `rgbaToThumbHash` by Evan Wallace.
+fn thumbhash(image: &DynamicImage) -> Vec<u8> {
+    let small = image
+        .thumbnail(THUMBHASH_IMAGE_SIZE, THUMBHASH_IMAGE_SIZE)
+        .into_rgba8();
+    let (w, h) = (small.width() as usize, small.height() as usize);
+    let pixels = small.pixels().map(|pixel| pixel.0.map(f32::from));
+
+    // average color, weighted by alpha
+    let (mut avg_r, mut avg_g, mut avg_b, mut avg_a) = (0., 0., 0., 0.);
+    for [r, g, b, a] in pixels.clone() {
+        let alpha = a / 255.;
+        avg_r += alpha / 255. * r;
+        avg_g += alpha / 255. * g;
+        avg_b += alpha / 255. * b;
+        avg_a += alpha;
+    }
+    if avg_a > 0. {
+        avg_r /= avg_a;
+        avg_g /= avg_a;
+        avg_b /= avg_a;
+    }
+
+    let has_alpha = avg_a < (w * h) as f32;
+    let l_limit = if has_alpha { 5. } else { 7. }; // fewer luminance bits if there is alpha
+    let max_size = w.max(h) as f32;
+    let lx = ((l_limit * w as f32 / max_size).round() as usize).max(1);
+    let ly = ((l_limit * h as f32 / max_size).round() as usize).max(1);
+
+    // convert to LPQA, composited atop the average color
+    let mut l = Vec::with_capacity(w * h); // luminance
+    let mut p = Vec::with_capacity(w * h); // yellow - blue
+    let mut q = Vec::with_capacity(w * h); // red - green
+    let mut a = Vec::with_capacity(w * h); // alpha
+    for [r, g, b, alpha] in pixels {
+        let alpha = alpha / 255.;
+        let r = avg_r * (1. - alpha) + alpha / 255. * r;
+        let g = avg_g * (1. - alpha) + alpha / 255. * g;
+        let b = avg_b * (1. - alpha) + alpha / 255. * b;
+        l.push((r + g + b) / 3.);
+        p.push((r + g) / 2. - b);
+        q.push(r - g);
+        a.push(alpha);
+    }
+
+    // DCT into the constant (DC) and normalized varying (AC) terms
+    let encode_channel = |channel: &[f32], nx: usize, ny: usize| {
+        let (mut dc, mut ac, mut scale) = (0., Vec::new(), 0_f32);
+        let mut fx = vec![0.; w];
+        for cy in 0..ny {
+            let mut cx = 0;
+            while cx * ny < nx * (ny - cy) {
+                for (x, f) in fx.iter_mut().enumerate() {
+                    *f = (PI / w as f32 * cx as f32 * (x as f32 + 0.5)).cos();
+                }
+                let mut f = 0.;
+                for y in 0..h {
+                    let fy = (PI / h as f32 * cy as f32 * (y as f32 + 0.5)).cos();
+                    for x in 0..w {
+                        f += channel[x + y * w] * fx[x] * fy;
+                    }
+                }
+                f /= (w * h) as f32;
+                if cx > 0 || cy > 0 {
+                    ac.push(f);
+                    scale = scale.max(f.abs());
+                } else {
+                    dc = f;
+                }
+                cx += 1;
+            }
+        }
+        if scale > 0. {
+            for f in ac.iter_mut() {
+                *f = 0.5 + 0.5 / scale * *f;
+            }
+        }
+        (dc, ac, scale)
+    };
+    let (l_dc, l_ac, l_scale) = encode_channel(&l, lx.max(3), ly.max(3));
+    let (p_dc, p_ac, p_scale) = encode_channel(&p, 3, 3);
+    let (q_dc, q_ac, q_scale) = encode_channel(&q, 3, 3);
+    let (a_dc, a_ac, a_scale) = if has_alpha {
+        encode_channel(&a, 5, 5)
+    } else {
+        (0., Vec::new(), 0.)
+    };
+
+    // constants
+    let is_landscape = w > h;
+    let header24 = (63. * l_dc).round() as u32
+        | ((31.5 + 31.5 * p_dc).round() as u32) << 6
+        | ((31.5 + 31.5 * q_dc).round() as u32) << 12
+        | ((31. * l_scale).round() as u32) << 18
+        | (has_alpha as u32) << 23;
+    let header16 = (if is_landscape { ly } else { lx }) as u32
+        | ((63. * p_scale).round() as u32) << 3
+        | ((63. * q_scale).round() as u32) << 9
+        | (is_landscape as u32) << 15;
+    let mut hash = vec![
+        (header24 & 255) as u8,
+        ((header24 >> 8) & 255) as u8,
+        (header24 >> 16) as u8,
+        (header16 & 255) as u8,
+        (header16 >> 8) as u8,
+    ];
+    if has_alpha {
+        hash.push((15. * a_dc).round() as u8 | ((15. * a_scale).round() as u8) << 4);
+    }
+
+    // varying factors, two per byte
+    let ac_start = hash.len();
+    let factors = [l_ac, p_ac, q_ac, a_ac].concat();
+    hash.resize(ac_start + factors.len().div_ceil(2), 0);
+    for (i, f) in factors.into_iter().enumerate() {
+        hash[ac_start + i / 2] |= ((15. * f).round() as u8) << ((i & 1) * 4);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, ImageFormat};
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::{Attribute, ObjectStore};
+
+    use crate::hash::{thumbhash, BLURHASH_METADATA, THUMBHASH_METADATA};
+    use crate::model::Params;
+    use crate::ImageThumbs;
+
+    #[test]
+    fn thumbhash_header() {
+        let opaque = DynamicImage::new_rgb8(200, 100);
+        let hash = thumbhash(&opaque);
+        assert_eq!(hash[2] >> 7, 0, "opaque images have no alpha");
+        assert_eq!(hash[4] >> 7, 1, "the image is landscape");
+        // 5 header bytes, 18 luminance and 2x5 color factors with 4 bits each
+        assert_eq!(hash.len(), 5 + (18 + 5 + 5) / 2);
+
+        let transparent = DynamicImage::new_rgba8(100, 200);
+        let hash = thumbhash(&transparent);
+        assert_eq!(hash[2] >> 7, 1, "transparent images have alpha");
+        assert_eq!(hash[4] >> 7, 0, "the image is portrait");
+    }
+
+    #[tokio::test]
+    async fn store_hashes() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            quality: 80,
+            size: (40, 40),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings)
+            .with_blurhash(4, 3)
+            .with_thumbhash();
+
+        let mut bytes = Vec::new();
+        DynamicImage::new_rgb8(400, 200)
+            .write_to(&mut std::io::Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        let hashes = client
+            .create_thumbs_from_bytes(
+                bytes,
+                "/thumbs",
+                "image",
+                ImageFormat::Png,
+                false,
+                (0.5, 0.5),
+            )
+            .await
+            .unwrap();
+        assert!(hashes.thumbhash.is_some());
+
+        let thumb = client
+            .client
+            .get(&Path::parse("thumbs/image_mini.png").unwrap())
+            .await
+            .unwrap();
+        let blurhash = thumb
+            .attributes
+            .get(&Attribute::Metadata(BLURHASH_METADATA.into()))
+            .unwrap();
+        assert_eq!(blurhash.as_ref(), hashes.blurhash.unwrap());
+        assert!(thumb
+            .attributes
+            .get(&Attribute::Metadata(THUMBHASH_METADATA.into()))
+            .is_some());
+    }
+}
@@ -0,0 +1,153 @@
+use image::ImageFormat;
+use object_store::{Attribute, Attributes, ObjectStore};
+
+use crate::model::{ContentDisposition, Params};
+use crate::ImageThumbs;
+
+/// File name offered for downloads if the preset does not set one
+const DEFAULT_FILENAME: &str = "{image_stem}_{thumb_name}";
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Adds the content type of the thumbnail and the response headers configured for the preset
+    /// to the attributes a thumbnail is stored with, so that they are served with the thumbnail
+    /// straight from the bucket. The content type is set explicitly, as stores only derive it from
+    /// the extensions JPEG and PNG.
+    ///
+    /// # Arguments
+    /// * `image_stem` - name of the source image without extension.
+    ///
+    /// * `format` - format the thumbnail is stored in.
+    pub(crate) fn insert_headers(
+        attributes: &mut Attributes,
+        params: &Params,
+        image_stem: &str,
+        format: ImageFormat,
+    ) {
+        attributes.insert(Attribute::ContentType, format.to_mime_type().into());
+        if let Some(cache_control) = &params.cache_control {
+            attributes.insert(Attribute::CacheControl, cache_control.clone().into());
+        }
+        if let Some(disposition) = &params.content_disposition {
+            let pattern = disposition.filename.as_deref().unwrap_or(DEFAULT_FILENAME);
+            let stem = Self::generate_thumb_stem(image_stem, params, pattern, format);
+            let stem = stem.rsplit('/').next().unwrap_or_default();
+            let filename = format!("{stem}.{}", format.extensions_str()[0]);
+            attributes.insert(
+                Attribute::ContentDisposition,
+                content_disposition(disposition, &filename).into(),
+            );
+        }
+    }
+}
+
+/// Value of the `Content-Disposition` header, with the file name encoded as in RFC 6266 if it is
+/// not plain ASCII
+fn content_disposition(disposition: &ContentDisposition, filename: &str) -> String {
+    let kind = if disposition.attachment {
+        "attachment"
+    } else {
+        "inline"
+    };
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii() && !c.is_ascii_control() => c,
+            _ => '_',
+        })
+        .collect();
+    if fallback == filename {
+        return format!("{kind}; filename=\"{filename}\"");
+    }
+    let encoded: String = filename
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect();
+    format!("{kind}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+#[cfg(test)]
+mod tests {
+    use image::ImageFormat;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::{Attribute, ObjectStore};
+
+    use super::content_disposition;
+    use crate::model::{ContentDisposition, Mode, Params};
+    use crate::test_utils::png;
+    use crate::ImageThumbs;
+
+    #[tokio::test]
+    async fn store_response_headers() {
+        let mut settings = vec![
+            Params::new("mini", (10, 10), Mode::Crop, 80),
+            Params::new("standard", (20, 20), Mode::Fit, 80),
+        ];
+        settings[0].cache_control = Some("public, max-age=31536000, immutable".to_string());
+        settings[1].content_disposition = Some(ContentDisposition {
+            attachment: true,
+            filename: Some("{image_stem}-{width}".to_string()),
+        });
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = png();
+        client
+            .create_thumbs_from_bytes(
+                bytes,
+                "thumbs",
+                "penguin",
+                ImageFormat::Png,
+                false,
+                (0.5, 0.5),
+            )
+            .await
+            .unwrap();
+
+        let store = &client.client;
+        let attributes = |path| async move {
+            store
+                .get(&Path::parse(path).unwrap())
+                .await
+                .unwrap()
+                .attributes
+        };
+        let mini = attributes("thumbs/penguin_mini.png").await;
+        assert_eq!(
+            mini.get(&Attribute::CacheControl).unwrap().as_ref(),
+            "public, max-age=31536000, immutable"
+        );
+        assert!(mini.get(&Attribute::ContentDisposition).is_none());
+        assert_eq!(
+            mini.get(&Attribute::ContentType).unwrap().as_ref(),
+            "image/png"
+        );
+        let standard = attributes("thumbs/penguin_standard.png").await;
+        assert!(standard.get(&Attribute::CacheControl).is_none());
+        assert_eq!(
+            standard
+                .get(&Attribute::ContentDisposition)
+                .unwrap()
+                .as_ref(),
+            "attachment; filename=\"penguin-20.png\""
+        );
+    }
+
+    #[test]
+    fn encode_content_disposition() {
+        let inline = ContentDisposition::default();
+        assert_eq!(
+            content_disposition(&inline, "penguin.png"),
+            "inline; filename=\"penguin.png\""
+        );
+        assert_eq!(
+            content_disposition(&inline, "pingüino \"1\".png"),
+            "inline; filename=\"ping_ino _1_.png\"; \
+             filename*=UTF-8''ping%C3%BCino%20%221%22.png"
+        );
+    }
+}
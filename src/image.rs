@@ -1,95 +1,676 @@
+use std::cmp::Reverse;
 use std::io::Cursor;
+use std::time::Instant;
 
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
 use image::codecs::jpeg::JpegEncoder;
 use image::codecs::png;
 use image::codecs::png::{CompressionType, PngEncoder};
-use image::{imageops, DynamicImage, GenericImageView};
-use image::{load_from_memory_with_format, ImageFormat};
+use image::metadata::Orientation;
+use image::ImageFormat;
+use image::{
+    DynamicImage, GenericImageView, ImageDecoder, ImageEncoder, ImageError, ImageReader, RgbImage,
+    RgbaImage,
+};
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
 use object_store::path::Path;
-use object_store::ObjectStore;
+use object_store::{Attribute, Attributes, ObjectStore};
 
-use crate::model::{ImageDetails, Mode, Params};
+use crate::dir::SOURCE_ETAG_METADATA;
+use crate::hash::{image_hashes, stable_hash, ImageHashes, BLURHASH_METADATA, THUMBHASH_METADATA};
+use crate::model::{Center, CreateOptions, ImageDetails, Mask, Mode, Params, Rect, Rotation, Tone};
+use crate::provenance::{insert_provenance, SOURCE_PATH_METADATA};
+use crate::refresh::PRESET_METADATA;
+use crate::resize::{resize_exact, resize_fill, resize_fit};
+use crate::saliency::{entropy_center, salient_center};
+use crate::storage::SOURCE_HASH_PLACEHOLDER;
+use crate::timeout::with_timeout;
 use crate::{Error, ImageThumbs, ThumbsResult};
 
 impl<T: ObjectStore> ImageThumbs<T> {
     pub(crate) async fn create_thumb_images_from_bytes(
         &self,
-        bytes: Vec<u8>,
+        bytes: Bytes,
         dest_dir: Path,
         stem: &str,
         format: ImageFormat,
-        force_override: bool,
-        center: (f32, f32),
-    ) -> ThumbsResult<Vec<ImageDetails>> {
-        let image = load_from_memory_with_format(&bytes, format)?;
-
-        let mut res = Vec::with_capacity(self.settings.len());
-        for params in self.settings.iter() {
-            let naming_pattern = params
-                .naming_pattern
-                .clone()
-                .unwrap_or("/{image_stem}_{thumb_name}".to_string());
-            let thumb_stem = Self::generate_thumb_stem(stem, &params.name, &naming_pattern);
-            if !force_override
-                && self
-                    .head(&Path::parse(Self::generate_path(
-                        &dest_dir,
-                        &thumb_stem,
-                        &format,
-                    ))?)
-                    .await
-                    .is_ok()
-            {
-                continue; // do not compute already existent thumbnails
+        options: CreateOptions<'_>,
+    ) -> ThumbsResult<(Vec<ImageDetails>, ImageHashes)> {
+        let presets = self.presets();
+        let settings = options.settings.unwrap_or(presets.as_slice());
+        let deadline = options
+            .timeouts
+            .unwrap_or(self.timeouts)
+            .encode
+            .map(|limit| Instant::now() + limit);
+        let crop = options.crop;
+        let center = options.center;
+        let blurhash = self.blurhash;
+        let thumbhash = self.thumbhash;
+        // faces are only a fallback for presets without a center of the call or their own
+        #[cfg(feature = "faces")]
+        let face_model = self.face_model.clone().filter(|_| {
+            center.is_none()
+                && settings
+                    .iter()
+                    .any(|p| matches!(p.mode, Mode::Crop) && p.center.is_none())
+        });
+        let to_srgb = settings.iter().any(|params| params.srgb);
+        let patterns = match options.naming_pattern {
+            Some(pattern) => vec![pattern],
+            None => settings
+                .iter()
+                .filter_map(|params| params.naming_pattern.as_deref())
+                .collect(),
+        };
+        let source_hash = patterns
+            .iter()
+            .any(|pattern| pattern.contains(SOURCE_HASH_PLACEHOLDER))
+            .then(|| stable_hash(&bytes));
+        // the work on the blocking thread pool cannot be interrupted, but stops after the
+        // current preset once the deadline passed
+        let remaining =
+            || deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        let decoding = run_blocking(move || -> ThumbsResult<_> {
+            let mut source = decode(&bytes, format)?;
+            if let Some(rect) = crop {
+                source.image = crop_to_rect(&source.image, rect)?;
             }
+            let hashes = image_hashes(&source.image, blurhash, thumbhash);
+            #[cfg(feature = "faces")]
+            let face_center = face_model.and_then(|model| model.face_center(&source.image));
+            #[cfg(not(feature = "faces"))]
+            let face_center = None;
+            let srgb = if to_srgb { source.to_srgb() } else { None };
+            Ok((source, srgb, hashes, face_center))
+        });
+        let (source, srgb, mut hashes, face_center) =
+            with_timeout(remaining(), "encoding", decoding)
+                .await
+                .map_err(|e| e.context("decoding", options.source_path.unwrap_or(stem)))?;
+        hashes.source_hash = source_hash;
 
-            let thumbnail = calculate_thumbnail(&image, params, center)?;
+        let mut attributes = Attributes::new();
+        if let Some(blurhash) = &hashes.blurhash {
+            attributes.insert(
+                Attribute::Metadata(BLURHASH_METADATA.into()),
+                blurhash.clone().into(),
+            );
+        }
+        if let Some(thumbhash) = &hashes.thumbhash {
+            attributes.insert(
+                Attribute::Metadata(THUMBHASH_METADATA.into()),
+                STANDARD.encode(thumbhash).into(),
+            );
+        }
 
-            let mut buf = Vec::new();
-            let writer = Cursor::new(&mut buf);
-            match format {
-                ImageFormat::Jpeg => {
-                    let encoder = JpegEncoder::new_with_quality(writer, params.quality);
-                    thumbnail.write_with_encoder(encoder)?;
-                }
-                ImageFormat::Png => {
-                    let encoder = PngEncoder::new_with_quality(
-                        writer,
-                        CompressionType::default(),
-                        png::FilterType::default(),
-                    );
-                    thumbnail.write_with_encoder(encoder)?;
-                }
-                _ => Err(Error::NotSupported)?,
+        if let Some(e_tag) = options.source_etag {
+            attributes.insert(
+                Attribute::Metadata(SOURCE_ETAG_METADATA.into()),
+                e_tag.to_string().into(),
+            );
+        }
+        if let Some(source_path) = options.source_path {
+            attributes.insert(
+                Attribute::Metadata(SOURCE_PATH_METADATA.into()),
+                source_path.to_string().into(),
+            );
+        }
+
+        let presets = settings.iter().filter(|params| {
+            options
+                .presets
+                .is_none_or(|names| names.contains(&params.name.as_str()))
+        });
+        let source_dimensions = source.image.dimensions();
+        let nested = options.nested.cloned().unwrap_or_default();
+        let mut pending = Vec::with_capacity(settings.len());
+        for params in presets {
+            if params.skip_smaller && params.covers(source_dimensions) {
+                continue; // the thumbnail would only be a copy of the image
+            }
+            let thumb_format = params.output_format(format);
+            let thumb_stem = match options.naming_pattern {
+                Some(pattern) => Self::generate_thumb_stem(stem, params, pattern, thumb_format),
+                None => Self::thumb_stem(stem, params, thumb_format),
             };
+            let thumb_stem = match &hashes.source_hash {
+                Some(hash) => thumb_stem.replace(SOURCE_HASH_PLACEHOLDER, hash),
+                None => thumb_stem,
+            };
+            let thumb_dir = params.thumb_dir(&dest_dir, &nested)?;
+            pending.push((params, thumb_dir, thumb_stem, thumb_format));
+        }
 
+        let jobs: Vec<_> = pending
+            .iter()
+            .map(|(params, _, _, thumb_format)| {
+                let params = Params {
+                    center: params.center.or(face_center),
+                    ..(*params).clone()
+                };
+                (params, *thumb_format)
+            })
+            .collect();
+        let rendering =
+            run_blocking(move || render_thumbs(&source, srgb.as_ref(), &jobs, center, deadline));
+        let thumbnails = with_timeout(remaining(), "encoding", rendering)
+            .await
+            .map_err(|e| {
+                e.context(
+                    "creating thumbnails of",
+                    options.source_path.unwrap_or(stem),
+                )
+            })?;
+
+        let mut res = Vec::with_capacity(pending.len());
+        for ((params, thumb_dir, thumb_stem, thumb_format), thumbnail) in
+            pending.into_iter().zip(thumbnails)
+        {
+            let mut attributes = attributes.clone();
+            attributes.insert(
+                Attribute::Metadata(PRESET_METADATA.into()),
+                params.fingerprint().into(),
+            );
+            Self::insert_headers(&mut attributes, params, stem, thumb_format);
+            insert_provenance(&mut attributes, params, thumbnail.dimensions);
             res.push(ImageDetails {
                 stem: thumb_stem,
-                format,
-                path: dest_dir.clone(),
-                bytes: buf,
+                format: thumb_format,
+                path: thumb_dir,
+                bytes: thumbnail.bytes.into(),
+                attributes,
+                dimensions: Some(thumbnail.dimensions),
+                e_tag: None,
             })
         }
-        Ok(res)
+        Ok((res, hashes))
+    }
+}
+
+/// Runs CPU-bound work on the blocking thread pool, so that it does not stall the other tasks of
+/// the async runtime.
+pub(crate) async fn run_blocking<R: Send + 'static>(
+    work: impl FnOnce() -> R + Send + 'static,
+) -> R {
+    match tokio::task::spawn_blocking(work).await {
+        Ok(result) => result,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
     }
 }
 
-fn calculate_thumbnail(
+/// Computes and encodes the thumbnails of all given presets in parallel, as the source is
+/// decoded only once.
+fn render_thumbs(
+    source: &SourceImage,
+    srgb: Option<&DynamicImage>,
+    jobs: &[(Params, ImageFormat)],
+    center: Option<Center>,
+    deadline: Option<Instant>,
+) -> ThumbsResult<Vec<RenderedThumb>> {
+    let render = |task: &Vec<usize>| render_cascade(source, srgb, jobs, task, center, deadline);
+    let tasks = render_tasks(jobs);
+
+    #[cfg(feature = "rayon")]
+    let mut rendered: Vec<_> = {
+        use rayon::prelude::*;
+        tasks.par_iter().flat_map(render).collect()
+    };
+    #[cfg(not(feature = "rayon"))]
+    let mut rendered: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = tasks
+            .iter()
+            .map(|task| scope.spawn(move || render(task)))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    });
+
+    rendered.sort_by_key(|(index, _)| *index);
+    rendered.into_iter().map(|(_, thumb)| thumb).collect()
+}
+
+/// Groups the presets into tasks of job indices that are computed in parallel. Presets that only
+/// resize are chained from large to small, so that each can be derived from the previous, larger
+/// thumbnail instead of the full-resolution source.
+fn render_tasks(jobs: &[(Params, ImageFormat)]) -> Vec<Vec<usize>> {
+    let (mut cascade, single): (Vec<usize>, Vec<usize>) =
+        (0..jobs.len()).partition(|&index| jobs[index].0.only_resizes());
+    cascade.sort_by_key(|&index| {
+        let params = &jobs[index].0;
+        let (width, height) = params.size;
+        (params.srgb, Reverse(width as u64 * height as u64))
+    });
+
+    let mut tasks: Vec<_> = single.into_iter().map(|index| vec![index]).collect();
+    if !cascade.is_empty() {
+        tasks.push(cascade);
+    }
+    tasks
+}
+
+/// Computes the thumbnails of the given jobs one after another, deriving each from the previous
+/// thumbnail if its size is nested within the previous preset's size.
+fn render_cascade(
+    source: &SourceImage,
+    srgb: Option<&DynamicImage>,
+    jobs: &[(Params, ImageFormat)],
+    task: &[usize],
+    center: Option<Center>,
+    deadline: Option<Instant>,
+) -> Vec<(usize, ThumbsResult<RenderedThumb>)> {
+    let mut previous: Option<(&Params, DynamicImage)> = None;
+    let mut res = Vec::with_capacity(task.len());
+    for &index in task {
+        let (params, format) = &jobs[index];
+        let (image, icc_profile) = match srgb {
+            Some(srgb) if params.srgb => (srgb, None),
+            _ => (&source.image, source.icc_profile.as_deref()),
+        };
+        let image = match &previous {
+            Some((previous_params, thumbnail))
+                if previous_params.srgb == params.srgb
+                    && params.size.0 <= previous_params.size.0
+                    && params.size.1 <= previous_params.size.1 =>
+            {
+                thumbnail
+            }
+            _ => image,
+        };
+
+        match render_thumb(image, params, center, *format, icc_profile, deadline) {
+            Ok((rendered, thumbnail)) => {
+                res.push((index, Ok(rendered)));
+                previous = Some((params, thumbnail));
+            }
+            Err(e) => {
+                res.push((index, Err(e)));
+                previous = None;
+            }
+        }
+    }
+    res
+}
+
+/// Computes and encodes the thumbnail of one preset, returning it also unencoded.
+///
+/// Fails if the computation finished after the deadline, so that the remaining presets are not
+/// computed anymore.
+fn render_thumb(
     image: &DynamicImage,
     params: &Params,
-    center: (f32, f32),
+    center: Option<Center>,
+    format: ImageFormat,
+    icc_profile: Option<&[u8]>,
+    deadline: Option<Instant>,
+) -> ThumbsResult<(RenderedThumb, DynamicImage)> {
+    // a color profile cannot be applied to a grayscale image, and the tone colors are sRGB
+    let icc_profile = icc_profile.filter(|_| !params.grayscale && params.tone.is_none());
+    let thumbnail = calculate_thumbnail(image, params, center)?;
+    let bytes = encode(&thumbnail, format, params.quality, icc_profile)?;
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        return Err(Error::Timeout("encoding"));
+    }
+    let rendered = RenderedThumb {
+        bytes,
+        dimensions: thumbnail.dimensions(),
+    };
+    Ok((rendered, thumbnail))
+}
+
+/// Encoded thumbnail of one preset
+struct RenderedThumb {
+    bytes: Vec<u8>,
+    dimensions: (u32, u32),
+}
+
+/// A decoded source image together with the metadata that is carried over into its thumbnails.
+pub(crate) struct SourceImage {
+    pub(crate) image: DynamicImage,
+    pub(crate) icc_profile: Option<Vec<u8>>,
+}
+
+impl SourceImage {
+    /// Converts the image from its embedded RGB color profile to sRGB.
+    ///
+    /// Returns `None` if there is no profile, or it cannot be applied, e.g., because it is broken
+    /// or not an RGB profile. The profile is then passed through to the thumbnails instead.
+    fn to_srgb(&self) -> Option<DynamicImage> {
+        let profile = ColorProfile::new_from_slice(self.icc_profile.as_deref()?).ok()?;
+        if profile.color_space != DataColorSpace::Rgb {
+            return None;
+        }
+
+        let (width, height) = self.image.dimensions();
+        let (layout, pixels) = if self.image.color().has_alpha() {
+            (Layout::Rgba, self.image.to_rgba8().into_raw())
+        } else {
+            (Layout::Rgb, self.image.to_rgb8().into_raw())
+        };
+        let transform = profile
+            .create_transform_8bit(
+                layout,
+                &ColorProfile::new_srgb(),
+                layout,
+                TransformOptions::default(),
+            )
+            .ok()?;
+        let mut converted = vec![0; pixels.len()];
+        transform.transform(&pixels, &mut converted).ok()?;
+
+        match layout {
+            Layout::Rgba => RgbaImage::from_raw(width, height, converted).map(DynamicImage::from),
+            _ => RgbImage::from_raw(width, height, converted).map(DynamicImage::from),
+        }
+    }
+}
+
+/// Decodes the image and applies its EXIF orientation, so that, e.g., phone photos are upright.
+/// A missing or unreadable orientation tag leaves the image as it is.
+pub(crate) fn decode(bytes: &[u8], format: ImageFormat) -> ThumbsResult<SourceImage> {
+    let decoding_error = |error| decoding_error(bytes, format, error);
+    let mut decoder = ImageReader::with_format(Cursor::new(bytes), format)
+        .into_decoder()
+        .map_err(decoding_error)?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let icc_profile = decoder.icc_profile().unwrap_or(None);
+    let mut image = DynamicImage::from_decoder(decoder).map_err(decoding_error)?;
+    image.apply_orientation(orientation);
+    Ok(SourceImage { image, icc_profile })
+}
+
+/// Width and height of the image after applying its EXIF orientation, read from its headers
+/// without decoding the pixels.
+pub(crate) fn oriented_dimensions(bytes: &[u8], format: ImageFormat) -> ThumbsResult<(u32, u32)> {
+    let mut decoder = ImageReader::with_format(Cursor::new(bytes), format).into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    Ok(
+        match decoder.orientation().unwrap_or(Orientation::NoTransforms) {
+            Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH => (height, width),
+            _ => (width, height),
+        },
+    )
+}
+
+/// Last bytes of every complete PNG file, the `IEND` chunk
+const PNG_END: [u8; 12] = [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82];
+/// Last bytes of every complete JPEG file, the end of image marker
+const JPEG_END: [u8; 2] = [0xFF, 0xD9];
+
+/// Reports invalid image data as [`Error::CorruptImage`], telling truncated data apart from
+/// garbage. Unsupported features and exceeded limits are returned as they are.
+fn decoding_error(bytes: &[u8], format: ImageFormat, error: ImageError) -> Error {
+    let truncated = match &error {
+        ImageError::IoError(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => true,
+        ImageError::Decoding(_) | ImageError::IoError(_) => match format {
+            ImageFormat::Png => !bytes.ends_with(&PNG_END),
+            ImageFormat::Jpeg => !bytes.ends_with(&JPEG_END),
+            _ => false,
+        },
+        _ => return error.into(),
+    };
+    Error::CorruptImage {
+        format,
+        length: bytes.len(),
+        truncated,
+        source: error,
+    }
+}
+
+/// Encodes the thumbnail in the given format and embeds the ICC profile of the source, if any,
+/// so that wide-gamut images keep their colors.
+pub(crate) fn encode(
+    thumbnail: &DynamicImage,
+    format: ImageFormat,
+    quality: u8,
+    icc_profile: Option<&[u8]>,
+) -> ThumbsResult<Vec<u8>> {
+    let mut buf = Vec::new();
+    let writer = Cursor::new(&mut buf);
+    match format {
+        ImageFormat::Jpeg => {
+            let mut encoder = JpegEncoder::new_with_quality(writer, quality);
+            if let Some(icc_profile) = icc_profile {
+                encoder
+                    .set_icc_profile(icc_profile.to_vec())
+                    .map_err(ImageError::Unsupported)?;
+            }
+            thumbnail.write_with_encoder(encoder)?;
+        }
+        ImageFormat::Png => {
+            let mut encoder = PngEncoder::new_with_quality(
+                writer,
+                CompressionType::default(),
+                png::FilterType::default(),
+            );
+            if let Some(icc_profile) = icc_profile {
+                encoder
+                    .set_icc_profile(icc_profile.to_vec())
+                    .map_err(ImageError::Unsupported)?;
+            }
+            thumbnail.write_with_encoder(encoder)?;
+        }
+        _ => Err(Error::NotSupported)?,
+    };
+    Ok(buf)
+}
+
+/// Crops the image to the given rectangle, which is cut off at the edges of the image.
+fn crop_to_rect(image: &DynamicImage, rect: Rect) -> ThumbsResult<DynamicImage> {
+    let (width, height) = image.dimensions();
+    if rect.x >= width || rect.y >= height || rect.width == 0 || rect.height == 0 {
+        return Err(Error::InvalidCrop);
+    }
+    Ok(image.crop_imm(
+        rect.x,
+        rect.y,
+        rect.width.min(width - rect.x),
+        rect.height.min(height - rect.y),
+    ))
+}
+
+/// Blur radius (sigma) applied to placeholders after scaling them down
+const PLACEHOLDER_BLUR: f32 = 1.;
+/// Sources with more pixels are shrunk with a fast box filter before the final resize
+const LARGE_SOURCE_PIXELS: u64 = 40_000_000;
+/// Minimum resolution kept by the pre-shrink, relative to the thumbnail size
+const PRE_SHRINK_MARGIN: f64 = 2.;
+
+pub(crate) fn calculate_thumbnail(
+    image: &DynamicImage,
+    params: &Params,
+    center: Option<Center>,
 ) -> ThumbsResult<DynamicImage> {
-    Ok(match params.mode {
+    let shrunk;
+    let image = match pre_shrink(image, params) {
+        Some(image) => {
+            shrunk = image;
+            &shrunk
+        }
+        None => image,
+    };
+
+    let rotated;
+    let image = match params.rotate {
+        Some(Rotation::Rotate90) => {
+            rotated = image.rotate90();
+            &rotated
+        }
+        Some(Rotation::Rotate180) => {
+            rotated = image.rotate180();
+            &rotated
+        }
+        Some(Rotation::Rotate270) => {
+            rotated = image.rotate270();
+            &rotated
+        }
+        None => image,
+    };
+
+    let thumbnail = match params.mode {
         Mode::Fit => {
-            let (width, height) = limit_size_fit(params.size, image.dimensions());
-            image.thumbnail(width, height)
+            let size = limit_size_fit(params.size, image.dimensions());
+            resize_fit(image, size)?
         }
-        Mode::Crop => {
+        Mode::Stretch => resize_exact(image, params.size)?,
+        Mode::Placeholder => {
+            let size = limit_size_fit(params.size, image.dimensions());
+            resize_fit(image, size)?.blur(PLACEHOLDER_BLUR)
+        }
+        Mode::Crop | Mode::SmartCrop => {
+            let center = center
+                .or(params.center.map(Center::from))
+                .unwrap_or_default();
+            let center = match (params.mode, center) {
+                (Mode::SmartCrop, _) => salient_center(image, params.size),
+                (_, Center::Manual(width, height)) => (width, height),
+                (_, Center::Auto) => entropy_center(image, params.size),
+            };
             let image = crop_aspect_ratio_with_center(image, params.size, center);
-            let (width, height) = limit_size_crop(params.size, image.dimensions());
-            image.resize_to_fill(width, height, imageops::FilterType::Nearest)
+            let size = limit_size_crop(params.size, image.dimensions());
+            resize_fill(&image, size)?
+        }
+    };
+
+    let thumbnail = if params.grayscale {
+        thumbnail.grayscale()
+    } else {
+        thumbnail
+    };
+
+    let thumbnail = match params.brightness {
+        Some(value) => thumbnail.brighten(value),
+        None => thumbnail,
+    };
+
+    let thumbnail = match params.contrast {
+        Some(percent) => thumbnail.adjust_contrast(percent),
+        None => thumbnail,
+    };
+
+    let thumbnail = match params.tone {
+        Some(tone) => apply_tone(thumbnail, tone),
+        None => thumbnail,
+    };
+
+    let thumbnail = match params.sharpen {
+        Some(sigma) => thumbnail.unsharpen(sigma, 0),
+        None => thumbnail,
+    };
+
+    let thumbnail = match params.blur {
+        Some(sigma) => thumbnail.blur(sigma),
+        None => thumbnail,
+    };
+
+    #[cfg(feature = "caption")]
+    let thumbnail = match &params.caption {
+        Some(caption) => crate::caption::draw_caption(thumbnail, caption)?,
+        None => thumbnail,
+    };
+
+    let thumbnail = match params.mask {
+        Some(mask) => apply_mask(thumbnail, mask),
+        None => thumbnail,
+    };
+
+    Ok(thumbnail)
+}
+
+/// Shrinks very large sources in a single box-filter pass to a size that still covers the
+/// thumbnail with some margin, so that the final resize (and cropping) work on far fewer pixels.
+///
+/// Returns `None` if the source is small enough to be resized directly.
+fn pre_shrink(image: &DynamicImage, params: &Params) -> Option<DynamicImage> {
+    let (width, height) = pre_shrink_size(image.dimensions(), params)?;
+    Some(image.thumbnail_exact(width, height))
+}
+
+fn pre_shrink_size((width, height): (u32, u32), params: &Params) -> Option<(u32, u32)> {
+    if (width as u64) * (height as u64) <= LARGE_SOURCE_PIXELS {
+        return None;
+    }
+
+    let (target_width, target_height) = match params.rotate {
+        Some(Rotation::Rotate90 | Rotation::Rotate270) => (params.size.1, params.size.0),
+        _ => params.size,
+    };
+    let scale = (target_width as f64 / width as f64).max(target_height as f64 / height as f64)
+        * PRE_SHRINK_MARGIN;
+    if scale >= 0.5 {
+        return None;
+    }
+    Some((
+        (width as f64 * scale).ceil() as u32,
+        (height as f64 * scale).ceil() as u32,
+    ))
+}
+
+/// Replaces the color of every pixel with a color of the tone, based on the pixel's brightness.
+fn apply_tone(image: DynamicImage, tone: Tone) -> DynamicImage {
+    let (dark, light) = match tone {
+        Tone::Duotone(dark, light) => (dark, light),
+        Tone::Tint(color) => ([0; 3], color),
+    };
+    let has_alpha = image.color().has_alpha();
+    let mut image = image.into_luma_alpha8();
+    let mut toned = RgbaImage::new(image.width(), image.height());
+    for (pixel, toned) in image.pixels_mut().zip(toned.pixels_mut()) {
+        let brightness = pixel[0] as f32 / 255.;
+        for channel in 0..3 {
+            let (dark, light) = (dark[channel] as f32, light[channel] as f32);
+            toned[channel] = (dark + (light - dark) * brightness).round() as u8;
         }
-    })
+        toned[3] = pixel[1];
+    }
+
+    let toned = DynamicImage::from(toned);
+    if has_alpha {
+        toned
+    } else {
+        DynamicImage::from(toned.into_rgb8())
+    }
+}
+
+/// Makes everything outside the mask transparent, with anti-aliased edges.
+fn apply_mask(image: DynamicImage, mask: Mask) -> DynamicImage {
+    let mut image = image.into_rgba8();
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let radius = match mask {
+        Mask::Circle => width.min(height) / 2.,
+        Mask::Rounded(radius) => radius.clamp(0., width.min(height) / 2.),
+    };
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+        // distance to the shape's edge; positive outside of it
+        let distance = match mask {
+            Mask::Circle => (px - width / 2.).hypot(py - height / 2.) - radius,
+            Mask::Rounded(_) => {
+                let dx = (radius - px).max(px - (width - radius)).max(0.);
+                let dy = (radius - py).max(py - (height - radius)).max(0.);
+                if dx > 0. && dy > 0. {
+                    dx.hypot(dy) - radius
+                } else {
+                    f32::NEG_INFINITY
+                }
+            }
+        };
+        let coverage = (0.5 - distance).clamp(0., 1.);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+    DynamicImage::from(image)
 }
 
 fn limit_size_fit(target_size: (u32, u32), original_size: (u32, u32)) -> (u32, u32) {
@@ -157,7 +738,7 @@ fn crop_aspect_ratio_with_center(
 
 #[cfg(test)]
 mod test {
-    use image::{ColorType, DynamicImage};
+    use image::{ColorType, DynamicImage, Luma};
 
     use super::*;
     use crate::model::{Mode, Params};
@@ -246,15 +827,262 @@ mod test {
         assert_eq!(cropped.height(), 100);
     }
 
+    #[test]
+    fn exif_orientation() {
+        // little-endian TIFF header with a single IFD entry: Orientation (0x0112) = 6 (rotate 90°)
+        let exif = vec![
+            0x49, 0x49, 0x2A, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01, 0x00, 0x12, 0x01, 0x03, 0x00,
+            0x01, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let image = DynamicImage::new(20, 10, ColorType::L8);
+        let mut buf = Vec::new();
+        let mut encoder = JpegEncoder::new(&mut buf);
+        encoder.set_exif_metadata(exif).unwrap();
+        image.write_with_encoder(encoder).unwrap();
+
+        let decoded = decode(&buf, ImageFormat::Jpeg).unwrap().image;
+        assert_eq!(decoded.width(), 10);
+        assert_eq!(decoded.height(), 20);
+    }
+
+    #[test]
+    fn keep_icc_profile() {
+        let icc_profile = vec![42; 128];
+        let image = DynamicImage::new(20, 10, ColorType::Rgb8);
+        for format in [ImageFormat::Jpeg, ImageFormat::Png] {
+            let encoded = encode(&image, format, 80, Some(&icc_profile)).unwrap();
+            let decoded = decode(&encoded, format).unwrap();
+            assert_eq!(decoded.icc_profile, Some(icc_profile.clone()));
+
+            let encoded = encode(&image, format, 80, None).unwrap();
+            let decoded = decode(&encoded, format).unwrap();
+            assert_eq!(decoded.icc_profile, None);
+        }
+    }
+
+    #[test]
+    fn convert_to_srgb() {
+        let image = DynamicImage::new(20, 10, ColorType::Rgb8);
+
+        let srgb = ColorProfile::new_srgb().encode().unwrap();
+        let source = SourceImage {
+            image: image.clone(),
+            icc_profile: Some(srgb),
+        };
+        let converted = source.to_srgb().unwrap();
+        assert_eq!(converted.dimensions(), (20, 10));
+
+        let broken = SourceImage {
+            image: image.clone(),
+            icc_profile: Some(vec![42; 128]),
+        };
+        assert!(broken.to_srgb().is_none());
+
+        let untagged = SourceImage {
+            image,
+            icc_profile: None,
+        };
+        assert!(untagged.to_srgb().is_none());
+    }
+
+    #[test]
+    fn sharpen() {
+        let mut image = DynamicImage::new(100, 100, ColorType::L8).into_luma8();
+        for (x, _, pixel) in image.enumerate_pixels_mut() {
+            pixel.0 = [if x < 50 { 100 } else { 150 }];
+        }
+        let image = DynamicImage::from(image);
+        let params = Params {
+            size: (50, 50),
+            sharpen: Some(1.),
+            ..Default::default()
+        };
+
+        let sharpened = calculate_thumbnail(&image, &params, None).unwrap();
+        assert_eq!(sharpened.dimensions(), (50, 50));
+        let edge = sharpened.to_luma8();
+        assert!(
+            edge.get_pixel(24, 25).0[0] < 100,
+            "dark side should get darker"
+        );
+        assert!(
+            edge.get_pixel(25, 25).0[0] > 150,
+            "bright side should get brighter"
+        );
+    }
+
+    #[test]
+    fn grayscale() {
+        let image = DynamicImage::new(100, 100, ColorType::Rgb8);
+        let params = Params {
+            size: (50, 50),
+            grayscale: true,
+            ..Default::default()
+        };
+
+        let thumbnail = calculate_thumbnail(&image, &params, None).unwrap();
+        assert_eq!(thumbnail.color(), ColorType::L8);
+    }
+
+    #[test]
+    fn blur() {
+        let mut image = DynamicImage::new(100, 100, ColorType::L8).into_luma8();
+        for (x, _, pixel) in image.enumerate_pixels_mut() {
+            pixel.0 = [if x < 50 { 0 } else { 255 }];
+        }
+        let image = DynamicImage::from(image);
+
+        for mode in [Mode::Fit, Mode::Crop] {
+            let params = Params {
+                size: (50, 50),
+                mode,
+                blur: Some(4.),
+                ..Default::default()
+            };
+            let blurred = calculate_thumbnail(&image, &params, None).unwrap();
+            assert_eq!(blurred.dimensions(), (50, 50));
+            let edge = blurred.to_luma8().get_pixel(25, 25).0[0];
+            assert!(edge > 0 && edge < 255, "the edge should be blurred");
+        }
+    }
+
+    #[test]
+    fn brightness_contrast() {
+        let mut image = DynamicImage::new(100, 100, ColorType::L8).into_luma8();
+        for (x, _, pixel) in image.enumerate_pixels_mut() {
+            pixel.0 = [if x < 50 { 60 } else { 200 }];
+        }
+        let image = DynamicImage::from(image);
+
+        let params = Params {
+            size: (50, 50),
+            brightness: Some(20),
+            ..Default::default()
+        };
+        let brighter = calculate_thumbnail(&image, &params, None).unwrap();
+        let brighter = brighter.to_luma8();
+        assert_eq!(brighter.get_pixel(0, 0).0, [80]);
+        assert_eq!(brighter.get_pixel(49, 0).0, [220]);
+
+        let params = Params {
+            size: (50, 50),
+            contrast: Some(50.),
+            ..Default::default()
+        };
+        let contrasted = calculate_thumbnail(&image, &params, None).unwrap();
+        let contrasted = contrasted.to_luma8();
+        assert!(contrasted.get_pixel(0, 0)[0] < 60);
+        assert!(contrasted.get_pixel(49, 0)[0] > 200);
+    }
+
+    #[test]
+    fn pre_shrink_large_source() {
+        let params = Params {
+            size: (100, 100),
+            ..Default::default()
+        };
+        assert_eq!(pre_shrink_size((10_000, 5_000), &params), Some((400, 200)));
+        assert_eq!(pre_shrink_size((1_000, 500), &params), None);
+
+        let params = Params {
+            size: (100, 50),
+            rotate: Some(Rotation::Rotate90),
+            ..Default::default()
+        };
+        assert_eq!(pre_shrink_size((10_000, 5_000), &params), Some((400, 200)));
+
+        let params = Params {
+            size: (5_000, 2_500),
+            ..Default::default()
+        };
+        assert_eq!(pre_shrink_size((10_000, 5_000), &params), None);
+    }
+
+    #[test]
+    fn tone() {
+        let mut image = DynamicImage::new(2, 1, ColorType::L8).into_luma8();
+        image.put_pixel(1, 0, Luma([255]));
+        let image = DynamicImage::from(image);
+
+        let duotone = apply_tone(image.clone(), Tone::Duotone([20, 30, 90], [255, 210, 140]));
+        assert_eq!(duotone.color(), ColorType::Rgb8);
+        let duotone = duotone.into_rgb8();
+        assert_eq!(duotone.get_pixel(0, 0).0, [20, 30, 90]);
+        assert_eq!(duotone.get_pixel(1, 0).0, [255, 210, 140]);
+
+        let tint = apply_tone(image, Tone::Tint([255, 120, 0])).into_rgb8();
+        assert_eq!(tint.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(tint.get_pixel(1, 0).0, [255, 120, 0]);
+    }
+
+    #[test]
+    fn mask() {
+        let image = DynamicImage::new_rgb8(100, 100);
+
+        let circle = apply_mask(image.clone(), Mask::Circle).into_rgba8();
+        assert_eq!(circle.get_pixel(0, 0)[3], 0);
+        assert_eq!(circle.get_pixel(99, 99)[3], 0);
+        assert_eq!(circle.get_pixel(50, 50)[3], 255);
+        assert_eq!(circle.get_pixel(50, 2)[3], 255);
+        assert_eq!(circle.get_pixel(10, 10)[3], 0);
+
+        let rounded = apply_mask(image, Mask::Rounded(10.)).into_rgba8();
+        assert_eq!(rounded.get_pixel(0, 0)[3], 0);
+        assert_eq!(rounded.get_pixel(10, 10)[3], 255);
+        assert_eq!(rounded.get_pixel(50, 0)[3], 255);
+        assert_eq!(rounded.get_pixel(0, 50)[3], 255);
+
+        let params = Params {
+            mask: Some(Mask::Circle),
+            ..Default::default()
+        };
+        assert_eq!(params.output_format(ImageFormat::Jpeg), ImageFormat::Png);
+    }
+
+    #[test]
+    fn rotate() {
+        let image = DynamicImage::new(100, 50, ColorType::L8);
+        for (rotate, expected) in [
+            (None, (100, 50)),
+            (Some(Rotation::Rotate90), (50, 100)),
+            (Some(Rotation::Rotate180), (100, 50)),
+            (Some(Rotation::Rotate270), (50, 100)),
+        ] {
+            let params = Params {
+                size: (200, 200),
+                rotate,
+                ..Default::default()
+            };
+            let thumbnail = calculate_thumbnail(&image, &params, None).unwrap();
+            assert_eq!(thumbnail.dimensions(), expected);
+        }
+        assert!(Rotation::try_from(45).is_err());
+    }
+
+    #[test]
+    fn crop_rect() {
+        let image = DynamicImage::new(100, 50, ColorType::L8);
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        let cropped = crop_to_rect(&image, rect(10, 10, 20, 30)).unwrap();
+        assert_eq!(cropped.dimensions(), (20, 30));
+        let cropped = crop_to_rect(&image, rect(90, 40, 20, 30)).unwrap();
+        assert_eq!(cropped.dimensions(), (10, 10));
+        assert!(crop_to_rect(&image, rect(100, 0, 20, 30)).is_err());
+        assert!(crop_to_rect(&image, rect(0, 0, 0, 30)).is_err());
+    }
+
     #[test]
     fn correct_final_size_crop_square() {
         let image = DynamicImage::new(100, 100, ColorType::L8);
         let params = Params {
-            name: "".to_string(),
-            naming_pattern: None,
-            quality: 0,
-            size: (0, 0),
             mode: Mode::Crop,
+            ..Default::default()
         };
 
         for (target_size, expect_output) in [
@@ -269,7 +1097,7 @@ mod test {
                     size: target_size,
                     ..params.clone()
                 },
-                (0.5, 0.5),
+                None,
             )
             .unwrap();
             assert_eq!(cropped.width(), expect_output.0);
@@ -282,11 +1110,8 @@ mod test {
         let portrait = DynamicImage::new(100, 150, ColorType::L8);
         let landscape = DynamicImage::new(150, 100, ColorType::L8);
         let params = Params {
-            name: "".to_string(),
-            naming_pattern: None,
-            quality: 0,
-            size: (0, 0),
             mode: Mode::Crop,
+            ..Default::default()
         };
 
         for (image, (target_size, expect_output)) in [
@@ -305,7 +1130,7 @@ mod test {
                     size: target_size,
                     ..params.clone()
                 },
-                (0.5, 0.5),
+                None,
             )
             .unwrap();
             assert_eq!(cropped.width(), expect_output.0);
@@ -313,15 +1138,34 @@ mod test {
         }
     }
 
+    #[test]
+    fn correct_final_size_stretch() {
+        let image = DynamicImage::new(100, 150, ColorType::L8);
+        let params = Params {
+            mode: Mode::Stretch,
+            ..Default::default()
+        };
+
+        for target_size in [(10, 10), (200, 20), (30, 400)] {
+            let stretched = calculate_thumbnail(
+                &image,
+                &Params {
+                    size: target_size,
+                    ..params.clone()
+                },
+                None,
+            )
+            .unwrap();
+            assert_eq!(stretched.dimensions(), target_size);
+        }
+    }
+
     #[test]
     fn correct_final_size_fit_square() {
         let image = DynamicImage::new(100, 100, ColorType::L8);
         let params = Params {
-            name: "".to_string(),
-            naming_pattern: None,
-            quality: 0,
-            size: (0, 0),
             mode: Mode::Fit,
+            ..Default::default()
         };
 
         for (target_size, expect_output) in [
@@ -336,7 +1180,7 @@ mod test {
                     size: target_size,
                     ..params.clone()
                 },
-                (0.5, 0.5),
+                None,
             )
             .unwrap();
             assert_eq!(cropped.width(), expect_output.0);
@@ -349,11 +1193,8 @@ mod test {
         let portrait = DynamicImage::new(100, 150, ColorType::L8);
         let landscape = DynamicImage::new(150, 100, ColorType::L8);
         let params = Params {
-            name: "".to_string(),
-            naming_pattern: None,
-            quality: 0,
-            size: (0, 0),
             mode: Mode::Fit,
+            ..Default::default()
         };
 
         for (image, (target_size, expect_output)) in [
@@ -372,11 +1213,40 @@ mod test {
                     size: target_size,
                     ..params.clone()
                 },
-                (0.5, 0.5),
+                None,
             )
             .unwrap();
             assert_eq!(cropped.width(), expect_output.0);
             assert_eq!(cropped.height(), expect_output.1);
         }
     }
+
+    #[test]
+    fn cascade_nested_presets() {
+        let fit = |size| Params {
+            size,
+            ..Default::default()
+        };
+        let jobs = [
+            (fit((100, 100)), ImageFormat::Png),
+            (
+                Params {
+                    mode: Mode::Crop,
+                    ..fit((50, 50))
+                },
+                ImageFormat::Png,
+            ),
+            (fit((400, 400)), ImageFormat::Png),
+            (fit((200, 200)), ImageFormat::Png),
+        ];
+        assert_eq!(render_tasks(&jobs), [vec![1], vec![2, 3, 0]]);
+
+        let source = SourceImage {
+            image: DynamicImage::new_rgb8(800, 600),
+            icc_profile: None,
+        };
+        let rendered = render_thumbs(&source, None, &jobs, None, None).unwrap();
+        let dimensions: Vec<_> = rendered.iter().map(|thumb| thumb.dimensions).collect();
+        assert_eq!(dimensions, [(100, 75), (50, 50), (400, 300), (200, 150)]);
+    }
 }
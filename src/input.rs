@@ -0,0 +1,227 @@
+use image::{guess_format, ImageFormat};
+use object_store::ObjectStore;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::model::{Center, CreateOptions};
+use crate::{Error, ImageHashes, ImageThumbs, ThumbsResult};
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Like [`ImageThumbs::create_thumbs_from_bytes`], but reads the image from the given reader,
+    /// e.g., the body of an upload request, without buffering it in the caller first.
+    ///
+    /// A stream of bytes can be turned into a reader with `tokio_util::io::StreamReader`.
+    ///
+    /// # Arguments
+    /// * `reader` - source of the raw image bytes, read until its end.
+    ///
+    /// * `dest_dir` - directory to store all created thumbnails.
+    ///   This directory will be checked for already existent thumbnails if `force_override` is false.
+    ///
+    /// * `image_name` - name used for the created thumbnails. Should not include the extension.
+    ///
+    /// * `format` - format of the input image.
+    ///
+    /// * `force_override` - if `true` it will override already existent files with the same name.
+    ///   If false, it will preserve already existent files.
+    ///
+    /// * `center` - where to place the center of the image, if the edges need to be cut off,
+    ///   e.g., `(0.5, 0.5)` or [`Center::Auto`].
+    ///
+    /// Returns the placeholder hashes of the image, if enabled.
+    pub async fn create_thumbs_from_reader(
+        &self,
+        mut reader: impl AsyncRead + Unpin,
+        dest_dir: &str,
+        image_name: &str,
+        format: ImageFormat,
+        force_override: bool,
+        center: impl Into<Center>,
+    ) -> ThumbsResult<ImageHashes> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        self.create_thumbs_from_bytes(bytes, dest_dir, image_name, format, force_override, center)
+            .await
+    }
+
+    /// Reads an image from the local file system, e.g., the temporary file of an upload, creates
+    /// thumbnails for it, and puts them in the `dest_dir` directory of the store.
+    ///
+    /// The format is detected from the contents, and the thumbnails are named after the file
+    /// name without its extension.
+    ///
+    /// # Arguments
+    /// * `path` - local path of the image.
+    ///
+    /// * `dest_dir` - directory to store all created thumbnails.
+    ///   This directory will be checked for already existent thumbnails if `force_override` is false.
+    ///
+    /// * `force_override` - if `true` it will override already existent files with the same name.
+    ///   If false, it will preserve already existent files.
+    ///
+    /// Returns the placeholder hashes of the image, if enabled.
+    pub async fn create_thumbs_from_file(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        dest_dir: &str,
+        force_override: bool,
+    ) -> ThumbsResult<ImageHashes> {
+        let path = path.as_ref();
+        let image_name = path
+            .file_stem()
+            .ok_or(Error::NotSupported)?
+            .to_str()
+            .ok_or(Error::Utf)?;
+        let bytes = tokio::fs::read(path).await?;
+        let format = guess_format(&bytes)?;
+        let options = CreateOptions {
+            force_override,
+            ..Default::default()
+        };
+        self.create_thumbs_with_options(bytes.into(), dest_dir, image_name, format, options)
+            .await
+    }
+
+    /// Downloads an image from the given URL, e.g., of a third-party feed, creates thumbnails for
+    /// it, and puts them in the `dest_dir` directory of the store.
+    ///
+    /// The format is detected from the contents.
+    ///
+    /// # Arguments
+    /// * `url` - HTTP(S) URL of the image.
+    ///
+    /// * `dest_dir` - directory to store all created thumbnails.
+    ///   This directory will be checked for already existent thumbnails if `force_override` is false.
+    ///
+    /// * `image_name` - name used for the created thumbnails. Should not include the extension.
+    ///
+    /// * `force_override` - if `true` it will override already existent files with the same name.
+    ///   If false, it will preserve already existent files.
+    ///
+    /// Returns the placeholder hashes of the image, if enabled.
+    #[cfg(feature = "url")]
+    pub async fn create_thumbs_from_url(
+        &self,
+        url: &str,
+        dest_dir: &str,
+        image_name: &str,
+        force_override: bool,
+    ) -> ThumbsResult<ImageHashes> {
+        let response = reqwest::get(url).await?.error_for_status()?;
+        let bytes = response.bytes().await?;
+        let format = guess_format(&bytes)?;
+        let options = CreateOptions {
+            force_override,
+            ..Default::default()
+        };
+        self.create_thumbs_with_options(bytes, dest_dir, image_name, format, options)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::ImageFormat;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    use crate::model::Params;
+    use crate::test_utils::png;
+    use crate::{Error, ImageThumbs};
+
+    #[tokio::test]
+    async fn create_thumbs_from_reader() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = png();
+
+        client
+            .create_thumbs_from_reader(
+                bytes.as_slice(),
+                "thumbs",
+                "penguin",
+                ImageFormat::Png,
+                false,
+                (0.5, 0.5),
+            )
+            .await
+            .unwrap();
+        client
+            .client
+            .head(&Path::parse("thumbs/penguin_mini.png").unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn create_thumbs_from_file() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+
+        client
+            .create_thumbs_from_file("src/test/mock_data/testBucket/penguin.jpg", "thumbs", false)
+            .await
+            .unwrap();
+        client
+            .client
+            .head(&Path::parse("thumbs/penguin_mini.jpg").unwrap())
+            .await
+            .unwrap();
+
+        let result = client
+            .create_thumbs_from_file("src/test/mock_data/testBucket/missing.jpg", "thumbs", false)
+            .await;
+        assert!(matches!(result, Err(Error::Io(_))));
+    }
+
+    #[cfg(feature = "url")]
+    #[tokio::test]
+    async fn create_thumbs_from_url() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        let bytes = png();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await.unwrap();
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                bytes.len()
+            );
+            stream.write_all(header.as_bytes()).await.unwrap();
+            stream.write_all(&bytes).await.unwrap();
+        });
+
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        client
+            .create_thumbs_from_url(
+                &format!("http://{address}/feed/12345"),
+                "thumbs",
+                "penguin",
+                false,
+            )
+            .await
+            .unwrap();
+        client
+            .client
+            .head(&Path::parse("thumbs/penguin_mini.png").unwrap())
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,146 @@
+use object_store::path::Path;
+use object_store::ObjectStore;
+
+use crate::model::ThumbInfo;
+use crate::{ImageThumbs, ThumbsResult};
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Returns the existing thumbnails of one image in the `dest_dir` directory, in the order of
+    /// the presets. Thumbnails that were not created (yet) are left out.
+    ///
+    /// The thumbnail names are derived from the image name and the naming patterns of the
+    /// presets, e.g., to build image URLs.
+    ///
+    /// # Arguments
+    /// * `file` - image to list the thumbnails of, e.g., `penguin.jpg`.
+    ///
+    /// * `dest_dir` - directory the thumbnails are stored in.
+    pub async fn list_thumbs(&self, file: &str, dest_dir: &str) -> ThumbsResult<Vec<ThumbInfo>> {
+        let thumbs = self.thumb_paths(
+            &Path::parse(file)?,
+            &Self::resolve_dest_dir(dest_dir, file)?,
+            &Path::default(),
+        )?;
+
+        let mut res = Vec::with_capacity(thumbs.len());
+        for (params, path) in thumbs {
+            match self.retrying(|| self.client.head(&path)).await {
+                Ok(meta) => res.push(ThumbInfo {
+                    preset: params.name.clone(),
+                    meta,
+                }),
+                Err(object_store::Error::NotFound { .. }) => {}
+                Err(err) => Err(err)?,
+            }
+        }
+        Ok(res)
+    }
+
+    /// Returns the names of the presets whose thumbnail of one image is missing in the
+    /// `dest_dir` directory, e.g., to decide whether to enqueue its regeneration.
+    ///
+    /// # Arguments
+    /// * `file` - image to check the thumbnails of, e.g., `penguin.jpg`.
+    ///
+    /// * `dest_dir` - directory the thumbnails are stored in.
+    pub async fn missing_thumbs(&self, file: &str, dest_dir: &str) -> ThumbsResult<Vec<String>> {
+        let existent = self.list_thumbs(file, dest_dir).await?;
+        Ok(self
+            .presets_for(&Path::parse(file)?)?
+            .iter()
+            .filter(|params| !existent.iter().any(|thumb| thumb.preset == params.name))
+            .map(|params| params.name.clone())
+            .collect())
+    }
+
+    /// Checks whether the thumbnails of all presets exist for one image in the `dest_dir`
+    /// directory. See [`ImageThumbs::missing_thumbs`] for which of them are missing.
+    pub async fn has_all_thumbs(&self, file: &str, dest_dir: &str) -> ThumbsResult<bool> {
+        Ok(self.missing_thumbs(file, dest_dir).await?.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+
+    use crate::model::Params;
+    use crate::test_utils::client_with_images;
+    use crate::{ImageThumbs, ThumbReport};
+
+    #[tokio::test]
+    async fn list_and_check_thumbs() {
+        let settings = vec![
+            Params {
+                name: "mini".to_string(),
+                size: (10, 10),
+                ..Default::default()
+            },
+            Params {
+                name: "standard".to_string(),
+                size: (20, 20),
+                ..Default::default()
+            },
+        ];
+        let client = client_with_images(settings, &["penguin.png"]).await;
+        client
+            .create_thumb("penguin.png", "mini", "/thumbs", false)
+            .await
+            .unwrap();
+
+        let thumbs = client.list_thumbs("penguin.png", "/thumbs").await.unwrap();
+        assert_eq!(thumbs.len(), 1);
+        assert_eq!(thumbs[0].preset, "mini");
+        assert_eq!(thumbs[0].meta.location.as_ref(), "thumbs/penguin_mini.png");
+
+        assert!(!client
+            .has_all_thumbs("penguin.png", "/thumbs")
+            .await
+            .unwrap());
+        assert_eq!(
+            client
+                .missing_thumbs("penguin.png", "/thumbs")
+                .await
+                .unwrap(),
+            ["standard"]
+        );
+        assert!(client
+            .list_thumbs("puffin.png", "/thumbs")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn filter_existent_thumbs() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            naming_pattern: Some("/{thumb_name}/{image_stem}".to_string()),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let path = |path| Path::parse(path).unwrap();
+
+        let reports: Vec<_> = ["penguin.jpg", "puffin.png", "movie.mp4"]
+            .into_iter()
+            .map(|image| ThumbReport {
+                image: path(image),
+                dest_dir: path("thumbs"),
+                skipped: false,
+                thumbs: Vec::new(),
+                source_hash: None,
+                nested: Path::default(),
+            })
+            .collect();
+        let missing = client
+            .filter_existent_thumbs(&reports, &[path("thumbs/mini/penguin.jpg")])
+            .unwrap();
+        assert_eq!(
+            missing,
+            HashSet::from([path("puffin.png"), path("movie.mp4")])
+        );
+    }
+}
@@ -2,8 +2,16 @@
 //! Easy-to-use library to create image thumbnails from images existing on some (cloud) object
 //! storage or from disk.
 //!
-//! Currently implemented is a connection to Google Cloud Storage,
-//! but it can be easily extended to other providers.
+//! Currently implemented are connections to Google Cloud Storage, AWS S3 and S3-compatible
+//! services (see `ImageThumbs::new_aws` and `ImageThumbs::new_s3_compatible`), and the local file
+//! system (see `ImageThumbs::new_local`), but it can be easily extended to other providers.
+//!
+//! ## Features
+//! * `caption` - text captions drawn on top of the thumbnails, see the `caption` preset option
+//! * `faces` - face-aware cropping, see `ImageThumbs::with_face_model`
+//! * `fast_resize` - SIMD-accelerated resizing using `fast_image_resize`
+//! * `http_source` - source images read from any HTTP server, see `ImageThumbs::with_http_source`
+//! * `webdav` - WebDAV servers as storage backend, see `ImageThumbs::new_webdav`
 //!
 //! ## Supported formats
 //! PNG and JPEG are currently the only supported image formats.
@@ -15,6 +23,15 @@
 #![doc = include_str!("../examples/image_thumbs.yaml")]
 //! ```
 //!
+//! TOML and JSON files with the same structure work as well, e.g.:
+//! ```toml
+//! [[thumbs]]
+//! name = "standard"
+//! quality = 80
+//! size = [640, 480]
+//! mode = "fit"
+//! ```
+//!
 //! ## Google credentials
 //! This crate relies on [object_store](https://crates.io/crates/object_store) for the interaction
 //! with the storage backend.
@@ -31,14 +48,19 @@
 //! GOOGLE_SERVICE_ACCOUNT_KEY: JSON serialized service account key
 //! GOOGLE_BUCKET: bucket name
 //! GOOGLE_BUCKET_NAME: (alias) bucket name
+//! GOOGLE_STORAGE_CLASS: (optional) storage class of the thumbnails, e.g., STANDARD
+//! GOOGLE_PREDEFINED_ACL: (optional) predefined ACL of the thumbnails, e.g., public-read
 //! ```
 //!
+//! Alternatively, pass the bucket and service account to `ImageThumbs::new_with_credentials`,
+//! e.g., if several buckets are used in one process.
+//!
 //! Then use it in your code
 //! ```no_run
 //! # #[tokio::main]
 //! # async fn main() {
-//!     // Path to your thumbnail configuration yaml. You may specify the .yaml extension in the
-//!     // path, but you don't need to.
+//!     // Path to your thumbnail configuration. You may specify the extension, e.g., .yaml or
+//!     // .toml, in the path, but you don't need to.
 //!     let thumbs = image_thumbs::ImageThumbs::new("examples/image_thumbs")
 //!         .await
 //!         .unwrap();
@@ -53,22 +75,95 @@
 //! # }
 //! ```
 
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
 use ::image::ImageFormat;
+use bytes::Bytes;
 use config::Config;
+/// Re-export to configure stores, e.g., for [`ImageThumbs::with_source`], with the same version
+/// of `object_store` as used by this crate
+pub use object_store;
 use object_store::path::Path;
 use object_store::ObjectStore;
 use thiserror::Error;
+pub use tokio_util::sync::CancellationToken;
 
+pub use crate::aws::{AwsCredentials, S3Endpoint};
+pub use crate::batch::BatchOutcome;
+use crate::defaults::inherit_defaults;
+use crate::dir::nested_folders;
+pub use crate::dir::{DirOptions, DirProgress, DirSummary};
+pub use crate::dynamic::DynImageThumbs;
+use crate::env::{env_overrides, environment};
 pub use crate::error::Error;
 pub use crate::error::ThumbsResult;
+pub use crate::gcs::ServiceAccount;
+pub use crate::hash::ImageHashes;
+pub use crate::model::Center;
+use crate::model::CreateOptions;
 pub use crate::model::ImageThumbs;
-use crate::model::Params;
+pub use crate::model::Mode;
+pub use crate::model::Params;
+pub use crate::model::Rect;
+pub use crate::model::ReplicationSummary;
+pub use crate::model::ThumbInfo;
+pub use crate::model::{CreatedThumb, ThumbReport};
+pub use crate::placeholder::Placeholder;
+pub use crate::plan::{PlannedAction, PlannedThumb};
+pub use crate::rate_limit::RateLimit;
+pub use crate::retry::Retries;
+use crate::timeout::with_timeout;
+pub use crate::timeout::Timeouts;
+use crate::validate::validate;
+#[cfg(feature = "webdav")]
+pub use crate::webdav::WebDavCredentials;
 
+mod aws;
+mod batch;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "caption")]
+mod caption;
+mod checkpoint;
+mod checksum;
+mod defaults;
+mod delete;
+mod dir;
+mod dynamic;
+mod env;
 mod error;
+#[cfg(feature = "faces")]
+mod faces;
 mod gcs;
+mod hash;
+mod headers;
 mod image;
+mod input;
+mod inspect;
+mod local;
 mod model;
+mod multipart;
+mod placeholder;
+mod plan;
+mod profiles;
+mod provenance;
+mod rate_limit;
+mod refresh;
+mod reload;
+mod replication;
+mod resize;
+mod retry;
+mod saliency;
+mod source;
 mod storage;
+#[cfg(test)]
+mod test_utils;
+mod timeout;
+mod transfer;
+mod validate;
+#[cfg(feature = "webdav")]
+mod webdav;
 
 impl<T: ObjectStore> ImageThumbs<T> {
     /// Gets all images from one object storage level, creates thumbnails for each of them, and puts
@@ -78,7 +173,8 @@ impl<T: ObjectStore> ImageThumbs<T> {
     /// * `directory` - directory to create thumbnails for.
     ///   It will list all objects on this level and create thumbnails (if they do not already exist).
     ///
-    /// * `dest_dir` - directory to store all created thumbnails.
+    /// * `dest_dir` - directory to store all created thumbnails, or relative to the folder of
+    ///   every image if it starts with `./`, e.g., `./thumbs` for a subfolder per album.
     ///   This directory will be checked for already existent thumbnails, if `force_override` is false.
     ///
     /// * `force_override` - if `true` it will override already existent files with the same name.
@@ -89,22 +185,29 @@ impl<T: ObjectStore> ImageThumbs<T> {
         dest_dir: &str,
         force_override: bool,
     ) -> ThumbsResult<()> {
-        let prefix = match directory {
-            Some(p) => Some(Path::parse(p)?),
-            None => None,
-        };
-
-        let mut names = self.list_folder(prefix.as_ref()).await?;
-
-        if force_override {
-            let existent_thumbs = self.list_folder(Some(&Path::parse(dest_dir)?)).await?;
-            names = self.filter_existent_thumbs(names, &existent_thumbs)?;
-        }
+        self.create_thumbs_dir_in(&self.client, directory, dest_dir, force_override)
+            .await
+    }
 
-        for name in names {
-            self.create_thumbs(name.as_ref(), dest_dir, force_override)
-                .await?;
-        }
+    /// Like [`ImageThumbs::create_thumbs_dir`], but reads the images from and stores the
+    /// thumbnails in the given store for this call only, e.g., another bucket of a multi-bucket
+    /// application.
+    ///
+    /// If a separate source store is configured with [`ImageThumbs::with_source`], the images
+    /// are still read from there.
+    pub async fn create_thumbs_dir_in(
+        &self,
+        store: &dyn ObjectStore,
+        directory: Option<&str>,
+        dest_dir: &str,
+        force_override: bool,
+    ) -> ThumbsResult<()> {
+        let options = DirOptions {
+            force_override,
+            ..Default::default()
+        };
+        self.process_dir(store, directory, dest_dir, &options)
+            .await?;
         Ok(())
     }
 
@@ -114,29 +217,296 @@ impl<T: ObjectStore> ImageThumbs<T> {
     /// # Arguments
     /// * `file` - image to create thumbnails for.
     ///
-    /// * `dest_dir` - directory to store all created thumbnails.
+    /// * `dest_dir` - directory to store all created thumbnails, e.g., `thumbs`, or relative to
+    ///   the folder of the image if it starts with `./`, e.g., `./thumbs`. The folders of the
+    ///   image are not mirrored below it, see [`ImageThumbs::create_thumbs_mirrored`].
     ///   This directory will be checked for already existent thumbnails if `force_override` is false.
     ///
     /// * `force_override` - if `true` it will override already existent files with the same name.
     ///   If false, it will preserve already existent files.
+    ///
+    /// Returns the placeholder hashes of the image, if enabled with [`ImageThumbs::with_blurhash`]
+    /// or [`ImageThumbs::with_thumbhash`].
     pub async fn create_thumbs(
         &self,
         file: &str,
         dest_dir: &str,
         force_override: bool,
-    ) -> ThumbsResult<()> {
+    ) -> ThumbsResult<ImageHashes> {
         let image = self.download_image(file).await?;
-        self.create_thumbs_from_bytes(
-            image.bytes,
+        let dest_dir = Self::resolve_dest_dir(dest_dir, file)?;
+        let presets = self.presets_for(&Path::parse(file)?)?;
+        let options = CreateOptions {
+            settings: Some(&presets),
+            ..CreateOptions::for_image(file, &image, force_override)
+        };
+        self.create_thumbs_with_options(
+            image.bytes.clone(),
+            dest_dir.as_ref(),
+            &image.stem,
+            image.format,
+            options,
+        )
+        .await
+    }
+
+    /// Like [`ImageThumbs::create_thumbs`], but reads the image from and stores the thumbnails
+    /// in the given store for this call only, e.g., another bucket of a multi-bucket application.
+    ///
+    /// If a separate source store is configured with [`ImageThumbs::with_source`], the image is
+    /// still read from there.
+    pub async fn create_thumbs_in(
+        &self,
+        store: &dyn ObjectStore,
+        file: &str,
+        dest_dir: &str,
+        force_override: bool,
+    ) -> ThumbsResult<ImageHashes> {
+        let (_, hashes) = self
+            .create_thumbs_in_with_report(
+                store,
+                file,
+                dest_dir,
+                &Path::default(),
+                force_override,
+                None,
+            )
+            .await?;
+        Ok(hashes)
+    }
+
+    /// Like [`ImageThumbs::create_thumbs`], but returns the path, format, dimensions, and size of
+    /// every created thumbnail, e.g., to store them in a database without listing the bucket.
+    ///
+    /// See [`ImageThumbs::create_thumbs`] for the arguments.
+    pub async fn create_thumbs_with_report(
+        &self,
+        file: &str,
+        dest_dir: &str,
+        force_override: bool,
+    ) -> ThumbsResult<ThumbReport> {
+        let (report, _) = self
+            .create_thumbs_in_with_report(
+                &self.client,
+                file,
+                dest_dir,
+                &Path::default(),
+                force_override,
+                None,
+            )
+            .await?;
+        Ok(report)
+    }
+
+    /// Like [`ImageThumbs::create_thumbs`], but mirrors the folders of the image below `root` in
+    /// `dest_dir` and in the directories of presets with their own one, like a recursive
+    /// directory run of `root` does. Images with the same name in different folders then do not
+    /// collide, e.g., the thumbnails of `photos/2024/05/penguin.jpg` end up in `thumbs/2024/05`
+    /// for the root `photos` and `dest_dir` `thumbs`.
+    ///
+    /// # Arguments
+    /// * `file` - image to create thumbnails for.
+    ///
+    /// * `root` - directory of which the structure is mirrored, e.g., `photos`. `None` mirrors
+    ///   all folders of the image.
+    ///
+    /// * `dest_dir` - directory to mirror the folders in. Directories relative to the folder of
+    ///   the image, starting with `./`, are used as they are.
+    ///
+    /// * `force_override` - if `true` it will override already existent files with the same name.
+    ///   If false, it will preserve already existent files.
+    ///
+    /// Returns the placeholder hashes of the image, if enabled.
+    pub async fn create_thumbs_mirrored(
+        &self,
+        file: &str,
+        root: Option<&str>,
+        dest_dir: &str,
+        force_override: bool,
+    ) -> ThumbsResult<ImageHashes> {
+        let nested = match Self::is_relative_dest_dir(dest_dir) {
+            true => Path::default(),
+            false => {
+                let root = root.map(Path::parse).transpose()?;
+                nested_folders(&Path::parse(file)?, root.as_ref())
+            }
+        };
+        let dest_dir: Path = Self::resolve_dest_dir(dest_dir, file)?
+            .parts()
+            .chain(nested.parts())
+            .collect();
+        let (_, hashes) = self
+            .create_thumbs_in_with_report(
+                &self.client,
+                file,
+                dest_dir.as_ref(),
+                &nested,
+                force_override,
+                None,
+            )
+            .await?;
+        Ok(hashes)
+    }
+
+    /// Creates the thumbnails of the image in `dest_dir`, in which its `nested` folders are
+    /// already mirrored.
+    pub(crate) async fn create_thumbs_in_with_report(
+        &self,
+        store: &dyn ObjectStore,
+        file: &str,
+        dest_dir: &str,
+        nested: &Path,
+        force_override: bool,
+        timeouts: Option<Timeouts>,
+    ) -> ThumbsResult<(ThumbReport, ImageHashes)> {
+        let image = with_timeout(
+            timeouts.unwrap_or(self.timeouts).download,
+            "downloading",
+            self.download_image_from(self.source_or(store), file),
+        )
+        .await?;
+        let dest_dir = Self::resolve_dest_dir(dest_dir, file)?;
+        let presets = self.presets_for(&Path::parse(file)?)?;
+        let options = CreateOptions {
+            store: Some(store),
+            settings: Some(&presets),
+            timeouts,
+            nested: Some(nested),
+            ..CreateOptions::for_image(file, &image, force_override)
+        };
+        let (thumbs, hashes) = self
+            .create_and_upload_thumbs(
+                image.bytes.clone(),
+                dest_dir.as_ref(),
+                &image.stem,
+                image.format,
+                options,
+            )
+            .await?;
+        let report = ThumbReport {
+            image: Path::parse(file)?,
             dest_dir,
+            skipped: thumbs.is_empty(),
+            thumbs,
+            source_hash: hashes.source_hash.clone(),
+            nested: nested.clone(),
+        };
+        Ok((report, hashes))
+    }
+
+    /// Gets one image from the object storage, creates only the thumbnail of the given preset for
+    /// it, and puts it in the `dest_dir` directory.
+    ///
+    /// # Arguments
+    /// * `file` - image to create the thumbnail for.
+    ///
+    /// * `preset` - name of the thumbnail in the config, e.g., `standard`.
+    ///
+    /// * `dest_dir` - directory to store the created thumbnail.
+    ///   This directory will be checked for an already existent thumbnail if `force_override` is
+    ///   false.
+    ///
+    /// * `force_override` - if `true` it will override an already existent file with the same
+    ///   name. If false, it will preserve an already existent file.
+    ///
+    /// Returns the placeholder hashes of the image, if enabled.
+    pub async fn create_thumb(
+        &self,
+        file: &str,
+        preset: &str,
+        dest_dir: &str,
+        force_override: bool,
+    ) -> ThumbsResult<ImageHashes> {
+        let presets = self.presets_for(&Path::parse(file)?)?;
+        Self::preset(&presets, preset)?;
+        let image = self.download_image(file).await?;
+        let dest_dir = Self::resolve_dest_dir(dest_dir, file)?;
+        let options = CreateOptions {
+            presets: Some(&[preset]),
+            settings: Some(&presets),
+            ..CreateOptions::for_image(file, &image, force_override)
+        };
+        self.create_thumbs_with_options(
+            image.bytes.clone(),
+            dest_dir.as_ref(),
             &image.stem,
             image.format,
-            force_override,
-            (0.5, 0.5),
+            options,
         )
         .await
     }
 
+    /// Returns the thumbnail of the given preset for one image from the `dest_dir` directory. If
+    /// it does not exist yet, it is created and stored first, e.g., for an on-demand image
+    /// service.
+    ///
+    /// # Arguments
+    /// * `file` - image to get the thumbnail of.
+    ///
+    /// * `preset` - name of the thumbnail in the config, e.g., `standard`.
+    ///
+    /// * `dest_dir` - directory the thumbnails are stored in.
+    ///
+    /// Returns the format and encoded bytes of the thumbnail. If the preset skips images that are
+    /// not larger than its size, see `skip_smaller`, and the image is not, the format and bytes
+    /// of the image itself are returned instead, without storing a thumbnail.
+    pub async fn get_or_create_thumb(
+        &self,
+        file: &str,
+        preset: &str,
+        dest_dir: &str,
+    ) -> ThumbsResult<(ImageFormat, Bytes)> {
+        let presets = self.presets_for(&Path::parse(file)?)?;
+        Self::preset(&presets, preset)?;
+        let dest_dir = Self::resolve_dest_dir(dest_dir, file)?;
+        let (_, path) = self
+            .thumb_paths(&Path::parse(file)?, &dest_dir, &Path::default())?
+            .into_iter()
+            .find(|(thumb_params, _)| thumb_params.name == preset)
+            .ok_or_else(|| Error::UnknownPreset(preset.to_string()))?;
+
+        match self
+            .retrying(|| async { self.client.get(&path).await?.bytes().await })
+            .await
+        {
+            Ok(bytes) => {
+                let format =
+                    ImageFormat::from_extension(path.extension().ok_or(Error::NotSupported)?)
+                        .ok_or(Error::NotSupported)?;
+                return Ok((format, bytes));
+            }
+            Err(object_store::Error::NotFound { .. }) => {}
+            Err(err) => Err(err)?,
+        }
+
+        let image = self.download_image(file).await?;
+        let options = CreateOptions {
+            presets: Some(&[preset]),
+            settings: Some(&presets),
+            // the thumbnail is known to be missing
+            ..CreateOptions::for_image(file, &image, true)
+        };
+        let (thumbs, _) = self
+            .create_thumb_images_from_bytes(
+                image.bytes.clone(),
+                dest_dir,
+                &image.stem,
+                image.format,
+                options,
+            )
+            .await?;
+        let Some(thumb) = thumbs
+            .first()
+            .map(|thumb| (thumb.format, thumb.bytes.clone()))
+        else {
+            // the thumbnail would only be a copy of the image
+            return Ok((image.format, image.bytes));
+        };
+        // keeps a thumbnail that was created in the meantime
+        self.upload_thumbs(&self.client, thumbs, false).await?;
+        Ok(thumb)
+    }
+
     /// Gets one image from the object storage, creates thumbnails for it, and puts them in the
     /// `dest_dir` directory.
     /// This function allows providing a manual definition of the image center, i.e., the most
@@ -151,23 +521,32 @@ impl<T: ObjectStore> ImageThumbs<T> {
     /// * `force_override` - if `true` it will override already existent files with the same name.
     ///   If false, it will preserve already existent files.
     ///
-    /// # `center` - (width, height) in percent (i.e., between 0 and 1) where to place the center of
-    /// the image, if the edges need to be cut off.
+    /// * `center` - (width, height) in percent (i.e., between 0 and 1) where to place the center
+    ///   of the image, if the edges need to be cut off, e.g., a focus point selected in a CMS. Use
+    ///   [`Center::Auto`] to center on the most detailed region of the image instead.
+    ///
+    /// Returns the placeholder hashes of the image, if enabled.
     pub async fn create_thumbs_man_center(
         &self,
         file: &str,
         dest_dir: &str,
         force_override: bool,
-        center: (f32, f32),
-    ) -> ThumbsResult<()> {
+        center: impl Into<Center>,
+    ) -> ThumbsResult<ImageHashes> {
         let image = self.download_image(file).await?;
-        self.create_thumbs_from_bytes(
-            image.bytes,
-            dest_dir,
+        let dest_dir = Self::resolve_dest_dir(dest_dir, file)?;
+        let presets = self.presets_for(&Path::parse(file)?)?;
+        let options = CreateOptions {
+            center: Some(center.into()),
+            settings: Some(&presets),
+            ..CreateOptions::for_image(file, &image, force_override)
+        };
+        self.create_thumbs_with_options(
+            image.bytes.clone(),
+            dest_dir.as_ref(),
             &image.stem,
             image.format,
-            force_override,
-            center,
+            options,
         )
         .await
     }
@@ -178,7 +557,8 @@ impl<T: ObjectStore> ImageThumbs<T> {
     /// # Arguments
     /// * `bytes` - raw image bytes to create thumbnails for.
     ///
-    /// * `dest_dir` - directory to store all created thumbnails.
+    /// * `dest_dir` - directory to store all created thumbnails, or relative to the folder in
+    ///   `image_name` if it starts with `./`.
     ///   This directory will be checked for already existent thumbnails if `force_override` is false.
     ///
     /// * `image_name` - name used for the created thumbnails. Should not include the extension.
@@ -189,28 +569,311 @@ impl<T: ObjectStore> ImageThumbs<T> {
     ///
     /// * `force_override` - if `true` it will override already existent files with the same name.
     ///   If false, it will preserve already existent files.
+    ///
+    /// * `center` - where to place the center of the image, if the edges need to be cut off,
+    ///   e.g., `(0.5, 0.5)` or [`Center::Auto`].
+    ///
+    /// Returns the placeholder hashes of the image, if enabled.
     pub async fn create_thumbs_from_bytes(
         &self,
-        bytes: Vec<u8>,
+        bytes: impl Into<Bytes>,
         dest_dir: &str,
         image_name: &str,
         format: ImageFormat,
         force_override: bool,
-        center: (f32, f32),
-    ) -> ThumbsResult<()> {
-        let dest_dir = Path::parse(dest_dir)?;
+        center: impl Into<Center>,
+    ) -> ThumbsResult<ImageHashes> {
+        let options = CreateOptions {
+            force_override,
+            center: Some(center.into()),
+            ..Default::default()
+        };
+        self.create_thumbs_with_options(bytes.into(), dest_dir, image_name, format, options)
+            .await
+    }
 
-        let thumbs = self
+    /// Takes the raw bytes of an image and creates thumbnails for it, without storing them.
+    ///
+    /// # Arguments
+    /// * `bytes` - raw image bytes to create thumbnails for.
+    ///
+    /// * `image_name` - name used for the created thumbnails. Should not include the extension.
+    ///
+    /// * `format` - format of the input image.
+    ///
+    /// * `center` - where to place the center of the image, if the edges need to be cut off,
+    ///   e.g., `(0.5, 0.5)` or [`Center::Auto`].
+    ///
+    /// Returns the path (relative to the destination directory, following the naming pattern),
+    /// format, and encoded bytes of every thumbnail.
+    pub async fn create_thumbs_in_memory(
+        &self,
+        bytes: impl Into<Bytes>,
+        image_name: &str,
+        format: ImageFormat,
+        center: impl Into<Center>,
+    ) -> ThumbsResult<Vec<(String, ImageFormat, Bytes)>> {
+        let options = CreateOptions {
+            // nothing is stored, so there is nothing to check
+            force_override: true,
+            center: Some(center.into()),
+            ..Default::default()
+        };
+        let (thumbs, _) = self
             .create_thumb_images_from_bytes(
-                bytes,
-                dest_dir,
+                bytes.into(),
+                Path::default(),
                 image_name,
                 format,
-                force_override,
-                center,
+                options,
             )
             .await?;
-        self.upload_thumbs(thumbs).await
+
+        thumbs
+            .into_iter()
+            .map(|thumb| {
+                let path =
+                    Path::parse(Self::generate_path(&thumb.path, &thumb.stem, &thumb.format))?;
+                Ok((path.to_string(), thumb.format, thumb.bytes))
+            })
+            .collect()
+    }
+
+    /// Gets one image from the object storage, crops it to the given rectangle, creates
+    /// thumbnails for the cropped part, and puts them in the `dest_dir` directory.
+    ///
+    /// # Arguments
+    /// * `file` - image to create thumbnails for.
+    ///
+    /// * `dest_dir` - directory to store all created thumbnails.
+    ///   This directory will be checked for already existent thumbnails if `force_override` is false.
+    ///
+    /// * `force_override` - if `true` it will override already existent files with the same name.
+    ///   If false, it will preserve already existent files.
+    ///
+    /// * `crop` - part of the image in pixels, e.g., as chosen by a user. It is applied after the
+    ///   image has been rotated according to its EXIF orientation, and before the thumbnails are
+    ///   resized.
+    ///
+    /// Returns the placeholder hashes of the cropped image, if enabled.
+    pub async fn create_thumbs_cropped(
+        &self,
+        file: &str,
+        dest_dir: &str,
+        force_override: bool,
+        crop: Rect,
+    ) -> ThumbsResult<ImageHashes> {
+        let image = self.download_image(file).await?;
+        let dest_dir = Self::resolve_dest_dir(dest_dir, file)?;
+        let presets = self.presets_for(&Path::parse(file)?)?;
+        let options = CreateOptions {
+            crop: Some(crop),
+            settings: Some(&presets),
+            ..CreateOptions::for_image(file, &image, force_override)
+        };
+        self.create_thumbs_with_options(
+            image.bytes.clone(),
+            dest_dir.as_ref(),
+            &image.stem,
+            image.format,
+            options,
+        )
+        .await
+    }
+
+    /// Takes the raw bytes of an image, crops it to the given rectangle, creates thumbnails for
+    /// the cropped part, and puts them in the `dest_dir` directory.
+    ///
+    /// See [`ImageThumbs::create_thumbs_from_bytes`] and [`ImageThumbs::create_thumbs_cropped`]
+    /// for the arguments.
+    pub async fn create_thumbs_from_bytes_cropped(
+        &self,
+        bytes: impl Into<Bytes>,
+        dest_dir: &str,
+        image_name: &str,
+        format: ImageFormat,
+        force_override: bool,
+        crop: Rect,
+    ) -> ThumbsResult<ImageHashes> {
+        let options = CreateOptions {
+            force_override,
+            crop: Some(crop),
+            ..Default::default()
+        };
+        self.create_thumbs_with_options(bytes.into(), dest_dir, image_name, format, options)
+            .await
+    }
+
+    /// Like [`ImageThumbs::create_thumbs`], but creates the given presets instead of the
+    /// configured ones for this call only, e.g., one-off sizes in admin tooling.
+    ///
+    /// # Arguments
+    /// * `file` - image to create thumbnails for.
+    ///
+    /// * `dest_dir` - directory to store all created thumbnails.
+    ///   This directory will be checked for already existent thumbnails if `force_override` is false.
+    ///
+    /// * `force_override` - if `true` it will override already existent files with the same name.
+    ///   If false, it will preserve already existent files.
+    ///
+    /// * `params` - presets to create, see [`Params::new`].
+    ///
+    /// Returns the placeholder hashes of the image, if enabled.
+    pub async fn create_thumbs_with_params(
+        &self,
+        file: &str,
+        dest_dir: &str,
+        force_override: bool,
+        params: &[Params],
+    ) -> ThumbsResult<ImageHashes> {
+        let image = self.download_image(file).await?;
+        let dest_dir = Self::resolve_dest_dir(dest_dir, file)?;
+        let options = CreateOptions {
+            settings: Some(params),
+            ..CreateOptions::for_image(file, &image, force_override)
+        };
+        self.create_thumbs_with_options(
+            image.bytes.clone(),
+            dest_dir.as_ref(),
+            &image.stem,
+            image.format,
+            options,
+        )
+        .await
+    }
+
+    /// Like [`ImageThumbs::create_thumbs`], but names the thumbnails of all presets with the given
+    /// pattern instead of the configured ones, e.g., for a migration into a new layout.
+    ///
+    /// # Arguments
+    /// * `file` - image to create thumbnails for.
+    ///
+    /// * `dest_dir` - directory to store all created thumbnails.
+    ///   This directory will be checked for already existent thumbnails if `force_override` is false.
+    ///
+    /// * `force_override` - if `true` it will override already existent files with the same name.
+    ///   If false, it will preserve already existent files.
+    ///
+    /// * `naming_pattern` - e.g., `/{thumb_name}/{image_stem}` or `/{image_stem}_{width}x{height}`.
+    ///   The extension is always appended.
+    ///
+    /// Returns the placeholder hashes of the image, if enabled.
+    pub async fn create_thumbs_with_naming_pattern(
+        &self,
+        file: &str,
+        dest_dir: &str,
+        force_override: bool,
+        naming_pattern: &str,
+    ) -> ThumbsResult<ImageHashes> {
+        let image = self.download_image(file).await?;
+        let dest_dir = Self::resolve_dest_dir(dest_dir, file)?;
+        let presets = self.presets_for(&Path::parse(file)?)?;
+        let options = CreateOptions {
+            naming_pattern: Some(naming_pattern),
+            settings: Some(&presets),
+            ..CreateOptions::for_image(file, &image, force_override)
+        };
+        self.create_thumbs_with_options(
+            image.bytes.clone(),
+            dest_dir.as_ref(),
+            &image.stem,
+            image.format,
+            options,
+        )
+        .await
+    }
+
+    /// Like [`ImageThumbs::create_thumbs_from_bytes`], but creates the given presets instead of
+    /// the configured ones for this call only.
+    ///
+    /// See [`ImageThumbs::create_thumbs_from_bytes`] and
+    /// [`ImageThumbs::create_thumbs_with_params`] for the arguments.
+    pub async fn create_thumbs_from_bytes_with_params(
+        &self,
+        bytes: impl Into<Bytes>,
+        dest_dir: &str,
+        image_name: &str,
+        format: ImageFormat,
+        force_override: bool,
+        params: &[Params],
+    ) -> ThumbsResult<ImageHashes> {
+        let options = CreateOptions {
+            force_override,
+            settings: Some(params),
+            ..Default::default()
+        };
+        self.create_thumbs_with_options(bytes.into(), dest_dir, image_name, format, options)
+            .await
+    }
+
+    async fn create_thumbs_with_options(
+        &self,
+        bytes: Bytes,
+        dest_dir: &str,
+        image_name: &str,
+        format: ImageFormat,
+        options: CreateOptions<'_>,
+    ) -> ThumbsResult<ImageHashes> {
+        let (_, hashes) = self
+            .create_and_upload_thumbs(bytes, dest_dir, image_name, format, options)
+            .await?;
+        Ok(hashes)
+    }
+
+    async fn create_and_upload_thumbs(
+        &self,
+        bytes: Bytes,
+        dest_dir: &str,
+        image_name: &str,
+        format: ImageFormat,
+        options: CreateOptions<'_>,
+    ) -> ThumbsResult<(Vec<CreatedThumb>, ImageHashes)> {
+        let dest_dir = Self::resolve_dest_dir(dest_dir, image_name)?;
+
+        let (thumbs, hashes) = self
+            .create_thumb_images_from_bytes(bytes, dest_dir, image_name, format, options)
+            .await?;
+        let created = with_timeout(
+            options.timeouts.unwrap_or(self.timeouts).upload,
+            "uploading",
+            self.upload_thumbs(
+                options.store.unwrap_or(&self.client),
+                thumbs,
+                options.force_override,
+            ),
+        )
+        .await?;
+        Ok((created, hashes))
+    }
+
+    pub(crate) fn from_parts(client: T, settings: Vec<Params>) -> Self {
+        Self {
+            client,
+            settings: RwLock::new(Arc::new(settings)),
+            profiles: HashMap::new(),
+            prefix_profiles: Vec::new(),
+            source: None,
+            replicas: Vec::new(),
+            blurhash: None,
+            thumbhash: false,
+            timeouts: Timeouts::default(),
+            multipart_threshold: None,
+            retries: Retries::default(),
+            limiter: Default::default(),
+            replication_summary: Default::default(),
+            replication_pending: Default::default(),
+            #[cfg(feature = "faces")]
+            face_model: None,
+        }
+    }
+
+    /// Returns the preset with the given name from the presets of an image, see
+    /// [`ImageThumbs::presets_for`].
+    pub(crate) fn preset<'a>(presets: &'a [Params], name: &str) -> ThumbsResult<&'a Params> {
+        presets
+            .iter()
+            .find(|params| params.name == name)
+            .ok_or_else(|| Error::UnknownPreset(name.to_string()))
     }
 
     /// Extracts the settings from the given configuration file.
@@ -220,26 +883,474 @@ impl<T: ObjectStore> ImageThumbs<T> {
     #[doc = include_str!("../examples/image_thumbs.yaml")]
     /// ```
     ///
+    /// TOML (`.toml`) and JSON (`.json`) files with the same structure are supported as well;
+    /// the format is detected from the extension.
+    ///
+    /// Fields shared by most presets can be set once in a `defaults` block next to `thumbs`, e.g.,
+    /// `defaults: { quality: 80, mode: crop }`. Presets inherit every field they do not set.
+    ///
+    /// Fields of a preset can be overridden with environment variables named after the preset
+    /// and the field, e.g., `THUMBS__STANDARD__QUALITY=70` or `THUMBS__MINI__SIZE=60,60`.
+    ///
     /// # Arguments
-    /// * `config` - Path to the config file from the crate root (`.yaml` may be omitted)
+    /// * `config` - Path to the config file from the crate root (the extension may be omitted)
+    ///
+    /// Fails with [`Error::InvalidSettings`] listing all invalid fields, e.g., a quality outside
+    /// of 1 to 100 or a preset name that is used twice. Unknown fields, e.g., a misspelled
+    /// `qualit`, fail with [`Error::Config`].
     fn settings(config: &str) -> ThumbsResult<Vec<Params>> {
-        Ok(Config::builder()
-            .add_source(config::File::with_name(config))
-            .build()?
-            .get("thumbs")?)
+        load_settings(config::File::with_name(config))
     }
 }
 
+/// Reads and validates the presets from the `thumbs` key of the given configuration source,
+/// with the fields inherited from the `defaults` block and overridden by environment variables, see [`ImageThumbs::new`].
+pub(crate) fn load_settings(
+    source: impl config::Source + Send + Sync + 'static,
+) -> ThumbsResult<Vec<Params>> {
+    let builder = Config::builder().add_source(source);
+    let config = builder.clone().build()?;
+    let presets = config.get_array("thumbs")?;
+    let builder = inherit_defaults(builder, &config, "thumbs", &presets)?;
+    let settings: Vec<Params> = env_overrides(builder, presets, &environment())?
+        .build()?
+        .get("thumbs")?;
+    validate(&settings)?;
+    Ok(settings)
+}
+
 #[cfg(test)]
 mod tests {
-    use image::ImageFormat;
+    use image::{DynamicImage, GenericImageView, ImageFormat};
+    use object_store::memory::InMemory;
     use object_store::path::Path;
+    use object_store::{Attributes, ObjectStore};
     use sequential_test::sequential;
     use tokio::fs::File;
     use tokio::io::{AsyncReadExt, BufReader};
 
-    use crate::model::ImageDetails;
-    use crate::ImageThumbs;
+    use crate::hash::stable_hash;
+    use crate::image::encode;
+    use crate::model::{ImageDetails, Mode, Params};
+    use crate::test_utils::{client_with_images, png};
+    use crate::{Error, ImageThumbs};
+
+    #[test]
+    fn load_toml_and_json_settings() {
+        let dir = std::env::temp_dir().join(format!("image_thumbs_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let toml = dir.join("thumbs.toml");
+        std::fs::write(
+            &toml,
+            "[[thumbs]]\nname = \"mini\"\nquality = 80\nsize = [40, 40]\nmode = \"crop\"\n",
+        )
+        .unwrap();
+        let json = dir.join("thumbs.json");
+        std::fs::write(
+            &json,
+            r#"{"thumbs": [{"name": "mini", "quality": 80, "size": [40, 40], "mode": "fit"}]}"#,
+        )
+        .unwrap();
+
+        let settings = ImageThumbs::<InMemory>::settings(toml.to_str().unwrap()).unwrap();
+        assert_eq!(settings[0].name, "mini");
+        assert!(matches!(settings[0].mode, Mode::Crop));
+        let settings = ImageThumbs::<InMemory>::settings(json.to_str().unwrap()).unwrap();
+        assert_eq!(settings[0].size, (40, 40));
+        assert!(matches!(settings[0].mode, Mode::Fit));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn create_single_preset() {
+        let settings = vec![
+            Params {
+                name: "mini".to_string(),
+                size: (10, 10),
+                ..Default::default()
+            },
+            Params {
+                name: "standard".to_string(),
+                size: (20, 20),
+                ..Default::default()
+            },
+        ];
+        let client = client_with_images(settings, &["penguin.png"]).await;
+
+        client
+            .create_thumb("penguin.png", "standard", "/thumbs", false)
+            .await
+            .unwrap();
+        let thumbs = ImageThumbs::<InMemory>::list_folder(
+            &client.client,
+            Some(&Path::parse("thumbs").unwrap()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            thumbs,
+            [Path::parse("thumbs/penguin_standard.png").unwrap()]
+        );
+
+        let result = client
+            .create_thumb("penguin.png", "huge", "/thumbs", false)
+            .await;
+        assert!(matches!(result, Err(Error::UnknownPreset(_))));
+    }
+
+    #[tokio::test]
+    async fn create_thumbs_with_params() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = client_with_images(settings, &["penguin.png"]).await;
+
+        let params = [Params::new("banner", (30, 5), Mode::Crop, 80)];
+        client
+            .create_thumbs_with_params("penguin.png", "thumbs", false, &params)
+            .await
+            .unwrap();
+        let thumbs = ImageThumbs::<InMemory>::list_folder(
+            &client.client,
+            Some(&Path::parse("thumbs").unwrap()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(thumbs, [Path::parse("thumbs/penguin_banner.png").unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn crop_around_focus_point() {
+        let settings = vec![Params::new("square", (10, 10), Mode::Crop, 100)];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        // black left half, white right half
+        let image = DynamicImage::ImageLuma8(image::GrayImage::from_fn(40, 20, |x, _| {
+            image::Luma([if x < 20 { 0 } else { 255 }])
+        }));
+        let bytes = encode(&image, ImageFormat::Png, 100, None).unwrap();
+        client
+            .client
+            .put(&Path::parse("penguin.png").unwrap(), bytes.into())
+            .await
+            .unwrap();
+
+        for (center, dest_dir, expected) in [((0.0, 0.5), "left", 0), ((1.0, 0.5), "right", 255)] {
+            client
+                .create_thumbs_man_center("penguin.png", dest_dir, false, center)
+                .await
+                .unwrap();
+            let thumb = client
+                .client
+                .get(&Path::parse(format!("{dest_dir}/penguin_square.png")).unwrap())
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap();
+            let thumb = image::load_from_memory(&thumb).unwrap().to_luma8();
+            assert!(thumb.pixels().all(|pixel| pixel.0[0] == expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn crop_around_preset_center() {
+        let mut settings = vec![Params::new("square", (10, 10), Mode::Crop, 100)];
+        settings[0].center = Some((1.0, 0.5));
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        // black left half, white right half
+        let image = DynamicImage::ImageLuma8(image::GrayImage::from_fn(40, 20, |x, _| {
+            image::Luma([if x < 20 { 0 } else { 255 }])
+        }));
+        let bytes = encode(&image, ImageFormat::Png, 100, None).unwrap();
+        client
+            .client
+            .put(&Path::parse("penguin.png").unwrap(), bytes.into())
+            .await
+            .unwrap();
+
+        client
+            .create_thumbs("penguin.png", "preset", false)
+            .await
+            .unwrap();
+        client
+            .create_thumbs_man_center("penguin.png", "call", false, (0.0, 0.5))
+            .await
+            .unwrap();
+        for (dest_dir, expected) in [("preset", 255), ("call", 0)] {
+            let thumb = client
+                .client
+                .get(&Path::parse(format!("{dest_dir}/penguin_square.png")).unwrap())
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap();
+            let thumb = image::load_from_memory(&thumb).unwrap().to_luma8();
+            assert!(thumb.pixels().all(|pixel| pixel.0[0] == expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn create_thumbs_with_naming_pattern() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = client_with_images(settings, &["penguin.png"]).await;
+
+        client
+            .create_thumbs_with_naming_pattern(
+                "penguin.png",
+                "thumbs",
+                false,
+                "/{thumb_name}/{image_stem}",
+            )
+            .await
+            .unwrap();
+        client
+            .client
+            .head(&Path::parse("thumbs/mini/penguin.png").unwrap())
+            .await
+            .unwrap();
+
+        client
+            .create_thumbs_with_naming_pattern(
+                "penguin.png",
+                "thumbs",
+                false,
+                "/{format}/{image_stem}_{width}x{height}",
+            )
+            .await
+            .unwrap();
+        client
+            .client
+            .head(&Path::parse("thumbs/png/penguin_10x10.png").unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn report_corrupt_images() {
+        let client = ImageThumbs::from_parts(
+            InMemory::new(),
+            vec![Params::new("mini", (10, 10), Mode::Fit, 80)],
+        );
+        let bytes = png();
+        let truncated = bytes[..bytes.len() / 2].to_vec();
+        let error = client
+            .create_thumbs_from_bytes(
+                truncated.clone(),
+                "thumbs",
+                "penguin",
+                ImageFormat::Png,
+                false,
+                (0.5, 0.5),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.inner(),
+            Error::CorruptImage {
+                format: ImageFormat::Png,
+                length,
+                truncated: true,
+                ..
+            } if *length == truncated.len()
+        ));
+
+        let mut garbage = bytes[..16].to_vec();
+        garbage.extend([0; 64]);
+        garbage.extend(&bytes[bytes.len() - 12..]);
+        let error = client
+            .create_thumbs_from_bytes(
+                garbage,
+                "thumbs",
+                "penguin",
+                ImageFormat::Png,
+                false,
+                (0.5, 0.5),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.inner(),
+            Error::CorruptImage {
+                truncated: false,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn create_thumbs_with_source_hash() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            naming_pattern: Some("/{image_stem}_{source_hash}".to_string()),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = png();
+        let hash = stable_hash(&bytes);
+        let hashes = client
+            .create_thumbs_from_bytes(
+                bytes,
+                "thumbs",
+                "penguin",
+                ImageFormat::Png,
+                false,
+                (0.5, 0.5),
+            )
+            .await
+            .unwrap();
+        assert_eq!(hashes.source_hash, Some(hash.clone()));
+        client
+            .client
+            .head(&Path::parse(format!("thumbs/penguin_{hash}.png")).unwrap())
+            .await
+            .unwrap();
+        assert!(matches!(
+            client.thumb_paths(
+                &Path::parse("penguin.png").unwrap(),
+                &Path::parse("thumbs").unwrap(),
+                &Path::default()
+            ),
+            Err(Error::ContentDependentPath(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn skip_presets_larger_than_image() {
+        let mut settings = vec![
+            Params::new("mini", (10, 10), Mode::Fit, 80),
+            Params::new("large", (100, 100), Mode::Fit, 80),
+            Params::new("copy", (100, 100), Mode::Fit, 80),
+        ];
+        settings[0].skip_smaller = true;
+        settings[1].skip_smaller = true;
+        let client = client_with_images(settings, &["penguin.png"]).await;
+
+        let report = client
+            .create_thumbs_with_report("penguin.png", "thumbs", false)
+            .await
+            .unwrap();
+        let thumbs: Vec<_> = report
+            .thumbs
+            .iter()
+            .map(|thumb| thumb.path.as_ref())
+            .collect();
+        assert_eq!(
+            thumbs,
+            ["thumbs/penguin_mini.png", "thumbs/penguin_copy.png"]
+        );
+
+        let (format, bytes) = client
+            .get_or_create_thumb("penguin.png", "large", "thumbs")
+            .await
+            .unwrap();
+        assert_eq!(format, ImageFormat::Png);
+        assert_eq!(bytes, png());
+        assert!(client
+            .client
+            .head(&Path::parse("thumbs/penguin_large.png").unwrap())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn create_thumbs_with_report() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = client_with_images(settings, &["penguin.png"]).await;
+
+        let report = client
+            .create_thumbs_with_report("penguin.png", "thumbs", false)
+            .await
+            .unwrap();
+        assert!(!report.skipped);
+        assert_eq!(report.thumbs.len(), 1);
+        let thumb = &report.thumbs[0];
+        assert_eq!(thumb.path, Path::parse("thumbs/penguin_mini.png").unwrap());
+        assert_eq!(thumb.format, ImageFormat::Png);
+        assert_eq!(thumb.dimensions, (10, 5));
+        let meta = client.client.head(&thumb.path).await.unwrap();
+        assert_eq!(thumb.size, meta.size);
+
+        let report = client
+            .create_thumbs_with_report("penguin.png", "thumbs", false)
+            .await
+            .unwrap();
+        assert!(report.skipped);
+        assert!(report.thumbs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_or_create_thumb() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = client_with_images(settings, &["penguin.png"]).await;
+
+        let (format, created) = client
+            .get_or_create_thumb("penguin.png", "mini", "/thumbs")
+            .await
+            .unwrap();
+        assert_eq!(format, ImageFormat::Png);
+        let thumb = image::load_from_memory(&created).unwrap();
+        assert_eq!(thumb.dimensions(), (10, 5));
+
+        // afterward, the stored thumbnail is returned, even without its source
+        client
+            .client
+            .delete(&Path::parse("penguin.png").unwrap())
+            .await
+            .unwrap();
+        let (_, stored) = client
+            .get_or_create_thumb("penguin.png", "mini", "/thumbs")
+            .await
+            .unwrap();
+        assert_eq!(stored, created);
+    }
+
+    #[tokio::test]
+    async fn create_thumbs_in_memory() {
+        let settings = vec![
+            Params {
+                name: "mini".to_string(),
+                size: (10, 10),
+                ..Default::default()
+            },
+            Params {
+                name: "standard".to_string(),
+                naming_pattern: Some("/{thumb_name}/{image_stem}".to_string()),
+                size: (20, 20),
+                ..Default::default()
+            },
+        ];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = png();
+        let thumbs = client
+            .create_thumbs_in_memory(bytes, "penguin", ImageFormat::Png, (0.5, 0.5))
+            .await
+            .unwrap();
+
+        let names: Vec<_> = thumbs.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, ["penguin_mini.png", "standard/penguin.png"]);
+        let thumb = image::load_from_memory(&thumbs[0].2).unwrap();
+        assert_eq!(thumb.dimensions(), (10, 5));
+        assert!(ImageThumbs::<InMemory>::list_folder(&client.client, None)
+            .await
+            .unwrap()
+            .is_empty());
+    }
 
     #[tokio::test]
     #[ignore]
@@ -447,9 +1558,15 @@ mod tests {
             stem: "penguin_standard".to_string(),
             format: ImageFormat::Png,
             path: Path::parse("/thumbs").unwrap(),
-            bytes: vec![1, 2, 3, 4, 5, 6, 7, 8, 9],
+            bytes: vec![1, 2, 3, 4, 5, 6, 7, 8, 9].into(),
+            attributes: Attributes::new(),
+            dimensions: None,
+            e_tag: None,
         };
-        client.upload_thumbs(vec![broken_thumb]).await.unwrap();
+        client
+            .upload_thumbs(&client.client, vec![broken_thumb], true)
+            .await
+            .unwrap();
 
         client
             .create_thumbs_dir(Some("/"), "thumbs", false)
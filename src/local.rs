@@ -0,0 +1,65 @@
+use object_store::local::LocalFileSystem;
+
+use crate::model::Params;
+use crate::{ImageThumbs, ThumbsResult};
+
+impl ImageThumbs<LocalFileSystem> {
+    /// Creates new ImageThumbs instance that reads images from and stores thumbnails on disk.
+    ///
+    /// All paths passed to this instance, e.g., `file` and `dest_dir` of
+    /// [`ImageThumbs::create_thumbs`], are relative to `root`, just like they are relative to the
+    /// bucket for the cloud storage backends. Leading slashes are ignored.
+    ///
+    /// The local file system cannot store metadata, so image hashes are only returned, not stored
+    /// with the thumbnails.
+    ///
+    /// # Arguments
+    /// * `root` - Existing directory that serves as the root of the storage
+    ///
+    /// * `config` - Path to the config file from the crate root (the extension may be omitted)
+    pub async fn new_local(root: &str, config: &str) -> ThumbsResult<Self> {
+        Self::new_local_with_settings(root, Self::settings(config)?).await
+    }
+
+    pub async fn new_local_with_settings(root: &str, settings: Vec<Params>) -> ThumbsResult<Self> {
+        let client = LocalFileSystem::new_with_prefix(root)?;
+
+        Ok(Self::from_parts(client, settings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, ImageFormat};
+
+    use crate::image::encode;
+    use crate::model::Params;
+    use crate::ImageThumbs;
+
+    #[tokio::test]
+    async fn create_thumbs_on_disk() {
+        let root = std::env::temp_dir().join(format!("image_thumbs_local_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("images")).unwrap();
+        let image = DynamicImage::new_rgb8(40, 20);
+        let bytes = encode(&image, ImageFormat::Png, 100, None).unwrap();
+        std::fs::write(root.join("images/penguin.png"), bytes).unwrap();
+
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::new_local_with_settings(root.to_str().unwrap(), settings)
+            .await
+            .unwrap()
+            .with_blurhash(4, 3);
+        client
+            .create_thumbs("/images/penguin.png", "/thumbs", false)
+            .await
+            .unwrap();
+
+        let thumb = image::open(root.join("thumbs/penguin_mini.png")).unwrap();
+        assert_eq!((thumb.width(), thumb.height()), (10, 5));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
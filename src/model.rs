@@ -1,36 +1,394 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use bytes::Bytes;
 use image::ImageFormat;
 use object_store::path::Path;
+use object_store::{Attributes, ObjectMeta, ObjectStore};
 use serde::Deserialize;
+use tokio::sync::watch;
+
+use crate::hash::stable_hash;
+use crate::rate_limit::Limiter;
+use crate::retry::Retries;
+use crate::timeout::Timeouts;
+use crate::ThumbsResult;
 
 #[derive(Debug)]
 pub struct ImageThumbs<T> {
+    /// Store the thumbnails are written to. Used without any lock, as stores are `Sync` and all
+    /// requests only need a shared reference.
     pub(crate) client: T,
-    pub(crate) settings: Vec<Params>,
+    /// Presets, which can be replaced while the instance is in use, see
+    /// [`ImageThumbs::reload_settings`]
+    pub(crate) settings: RwLock<Arc<Vec<Params>>>,
+    /// Named groups of presets that can be created instead of the settings, see
+    /// [`ImageThumbs::with_profiles`]
+    pub(crate) profiles: HashMap<String, Arc<Vec<Params>>>,
+    /// Profiles applied to the source images of which the path matches the pattern
+    pub(crate) prefix_profiles: Vec<(glob::Pattern, String)>,
+    /// Store the source images are read from, if it differs from the one thumbnails are stored in
+    pub(crate) source: Option<Arc<dyn ObjectStore>>,
+    /// Secondary stores every thumbnail is copied to after a successful primary upload
+    pub(crate) replicas: Vec<Arc<dyn ObjectStore>>,
+    pub(crate) replication_summary: Arc<Mutex<ReplicationSummary>>,
+    /// Number of replica uploads running in the background
+    pub(crate) replication_pending: Arc<watch::Sender<usize>>,
+    /// Number of BlurHash components, if BlurHashes should be computed
+    pub(crate) blurhash: Option<(u32, u32)>,
+    pub(crate) thumbhash: bool,
+    pub(crate) timeouts: Timeouts,
+    /// Size in bytes above which thumbnails are uploaded in parts
+    pub(crate) multipart_threshold: Option<usize>,
+    pub(crate) retries: Retries,
+    pub(crate) limiter: Limiter,
+    #[cfg(feature = "faces")]
+    pub(crate) face_model: Option<crate::faces::FaceModel>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+/// Version of the options included in [`Params::fingerprint`]
+const FINGERPRINT_VERSION: &str = "v1";
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
 pub struct Params {
     /// Can be used as `{thumb_name}` in the `naming_pattern`.
     /// If the naming_pattern is not explicitly given, the default is
     /// "`/{thumb_name}/{image_name}.{image_extension}`"
     pub(crate) name: String,
+    /// Directory the thumbnails are stored in instead of the one given for the call, e.g.,
+    /// `/thumbs/mini` for CDNs that expect a directory per size. The folders of the image that
+    /// are mirrored below the directory of the call, e.g., by a recursive directory run, are
+    /// mirrored below it as well.
+    pub(crate) dest_dir: Option<String>,
+    /// Path of the thumbnail below the destination directory, without extension. Besides
+    /// `{image_stem}` and `{thumb_name}`, it can contain the target `{width}` and `{height}`, the
+    /// `{format}` and `{quality}` of the thumbnail, a `{hash}` of the preset's options, and a
+    /// `{source_hash}` of the contents of the image, e.g., for immutable URLs. The latter is
+    /// returned with the hashes, and thumbnails named with it cannot be found without the image.
     pub(crate) naming_pattern: Option<String>,
     /// PNG ignores this variable as it is always lossless
     pub(crate) quality: u8,
     pub(crate) size: (u32, u32),
     pub(crate) mode: Mode,
+    /// Converts images with an embedded color profile to sRGB instead of embedding the profile
+    /// in the thumbnail
+    #[serde(default)]
+    pub(crate) srgb: bool,
+    /// Strength (sigma) of the unsharp mask applied after resizing, e.g., `0.5`
+    pub(crate) sharpen: Option<f32>,
+    /// Creates a monochrome thumbnail
+    #[serde(default)]
+    pub(crate) grayscale: bool,
+    /// Added to every color channel, e.g., `20` to lift dark photos; negative values darken
+    pub(crate) brightness: Option<i32>,
+    /// Contrast change in percent, e.g., `10.0`; negative values reduce the contrast
+    pub(crate) contrast: Option<f32>,
+    /// Recolors the thumbnail, e.g., in brand colors
+    pub(crate) tone: Option<Tone>,
+    /// Radius (sigma) of the Gaussian blur applied after resizing, e.g., `8.0` for a preview that
+    /// hides all details
+    pub(crate) blur: Option<f32>,
+    /// Makes the corners of the thumbnail transparent. Masked thumbnails are always stored as PNG.
+    pub(crate) mask: Option<Mask>,
+    /// Clockwise rotation applied before cropping and resizing, for sources that are known to be
+    /// stored rotated
+    pub(crate) rotate: Option<Rotation>,
+    /// Where to place the center of cropped thumbnails, if no center is given for the call, e.g.,
+    /// `[0.5, 0.25]` to favor the top of the image for banners. Defaults to the middle.
+    pub(crate) center: Option<(f32, f32)>,
+    /// Skips the preset for images that are not larger than its size, instead of storing a
+    /// thumbnail of the same size as the image
+    #[serde(default)]
+    pub(crate) skip_smaller: bool,
+    /// `Cache-Control` header the thumbnails are served with, e.g.,
+    /// `public, max-age=31536000, immutable`. Can be set for all presets in the `defaults` block.
+    pub(crate) cache_control: Option<String>,
+    /// `Content-Disposition` header the thumbnails are served with, e.g., to offer readable file
+    /// names for downloads
+    pub(crate) content_disposition: Option<ContentDisposition>,
+    /// Text drawn on top of the thumbnail
+    #[cfg(feature = "caption")]
+    pub(crate) caption: Option<crate::caption::Caption>,
+}
+
+impl Params {
+    /// Creates a preset with the required options, e.g., to create one-off sizes with
+    /// [`ImageThumbs::create_thumbs_with_params`]. All other options are disabled.
+    ///
+    /// # Arguments
+    /// * `name` - name of the thumbnail, added to the image name with an underscore (`_`).
+    ///
+    /// * `size` - target width and height of the thumbnail.
+    ///
+    /// * `mode` - how the image is fitted into the size.
+    ///
+    /// * `quality` - JPEG quality between 1 and 100, ignored for PNG.
+    pub fn new(name: &str, size: (u32, u32), mode: Mode, quality: u8) -> Self {
+        Self {
+            name: name.to_string(),
+            size,
+            mode,
+            quality,
+            ..Default::default()
+        }
+    }
+
+    /// Stable hash of the options that change the pixels of the thumbnail, to detect thumbnails
+    /// created with a different version of the preset. Options that only affect where or how the
+    /// thumbnail is stored, e.g., its name or headers, are left out, so that changing them does
+    /// not rename or recreate existent thumbnails.
+    ///
+    /// New options must only be added to the hash if they are set, and the version must be raised
+    /// if the meaning of an option changes.
+    pub(crate) fn fingerprint(&self) -> String {
+        let mut options = vec![
+            FINGERPRINT_VERSION.to_string(),
+            format!("size={:?}", self.size),
+            format!("mode={:?}", self.mode),
+            format!("quality={}", self.quality),
+            format!("mask={:?}", self.mask),
+        ];
+        let optional = [
+            ("srgb", self.srgb.then(|| "true".to_string())),
+            ("grayscale", self.grayscale.then(|| "true".to_string())),
+            ("sharpen", self.sharpen.map(|value| format!("{value:?}"))),
+            (
+                "brightness",
+                self.brightness.map(|value| format!("{value:?}")),
+            ),
+            ("contrast", self.contrast.map(|value| format!("{value:?}"))),
+            ("tone", self.tone.as_ref().map(|value| format!("{value:?}"))),
+            ("blur", self.blur.map(|value| format!("{value:?}"))),
+            (
+                "rotate",
+                self.rotate.as_ref().map(|value| format!("{value:?}")),
+            ),
+            ("center", self.center.map(|value| format!("{value:?}"))),
+            #[cfg(feature = "caption")]
+            (
+                "caption",
+                self.caption.as_ref().map(|value| format!("{value:?}")),
+            ),
+        ];
+        options.extend(
+            optional
+                .into_iter()
+                .filter_map(|(name, value)| Some(format!("{name}={}", value?))),
+        );
+        stable_hash(options.join(";").as_bytes())
+    }
+
+    /// Directory the thumbnails of this preset are stored in, given the one of the call and the
+    /// folders of the image that are mirrored below it. The call's directory already contains
+    /// them, the preset's own directory does not.
+    pub(crate) fn thumb_dir(&self, dest_dir: &Path, nested: &Path) -> ThumbsResult<Path> {
+        match &self.dest_dir {
+            Some(dir) => Ok(Path::parse(dir)?.parts().chain(nested.parts()).collect()),
+            None => Ok(dest_dir.clone()),
+        }
+    }
+
+    /// Whether a thumbnail with the given dimensions can have been created with this preset.
+    ///
+    /// Thumbnails of images smaller than the preset's size never match.
+    pub(crate) fn matches_size(&self, (width, height): (u32, u32)) -> bool {
+        let (target_width, target_height) = self.size;
+        match self.mode {
+            Mode::Fit | Mode::Placeholder => {
+                width <= target_width
+                    && height <= target_height
+                    && (width == target_width || height == target_height)
+            }
+            Mode::Crop | Mode::SmartCrop | Mode::Stretch => (width, height) == self.size,
+        }
+    }
+
+    /// Whether the image with the given dimensions fits into the preset's size, after rotating it,
+    /// so that its thumbnail would not be scaled down
+    pub(crate) fn covers(&self, (width, height): (u32, u32)) -> bool {
+        let (width, height) = match self.rotate {
+            Some(Rotation::Rotate90 | Rotation::Rotate270) => (height, width),
+            _ => (width, height),
+        };
+        width <= self.size.0 && height <= self.size.1
+    }
+
+    /// Whether the preset only resizes the image to fit, so that its thumbnail can be derived
+    /// from a larger thumbnail of the same image
+    pub(crate) fn only_resizes(&self) -> bool {
+        let only_resizes = matches!(self.mode, Mode::Fit)
+            && self.rotate.is_none()
+            && !self.grayscale
+            && self.brightness.is_none()
+            && self.contrast.is_none()
+            && self.tone.is_none()
+            && self.sharpen.is_none()
+            && self.blur.is_none()
+            && self.mask.is_none();
+        #[cfg(feature = "caption")]
+        let only_resizes = only_resizes && self.caption.is_none();
+        only_resizes
+    }
+
+    /// Format of the thumbnails created from a source image of the given format
+    pub(crate) fn output_format(&self, source: ImageFormat) -> ImageFormat {
+        if self.mask.is_some() {
+            ImageFormat::Png // needs an alpha channel
+        } else {
+            source
+        }
+    }
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+/// How the image is fitted into the size of a thumbnail.
+#[derive(Deserialize, Debug, Clone, Copy, Default)]
 #[serde(rename_all = "snake_case")]
-pub(crate) enum Mode {
+pub enum Mode {
     /// The image's aspect ratio is preserved. The image is scaled to the maximum possible size that
     /// fits within the bounds.
+    #[default]
     Fit,
     /// The image's aspect ratio is preserved. The image is scaled to the maximum possible size that
     /// fits within the larger (relative to aspect ratio) of the bounds, then cropped to fit within
     /// the other bound.
     Crop,
+    /// Like `Crop`, but the crop window is placed on the most salient part of the image, i.e., the
+    /// region with the most edges and contrast, instead of around the given center.
+    SmartCrop,
+    /// The image is scaled to exactly the bounds, without preserving its aspect ratio, e.g., for
+    /// sprite sheets that require exact dimensions.
+    Stretch,
+    /// Tiny, blurred version of the image for blur-up loading. The image is scaled to fit within
+    /// the bounds, e.g., `[20, 20]`, and then blurred.
+    Placeholder,
+}
+
+/// Color transform based on the brightness of each pixel. Colors are given as sRGB.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Tone {
+    /// Maps black to the first and white to the second color, e.g., `{ duotone: [[20, 30, 90],
+    /// [255, 210, 140]] }`
+    Duotone([u8; 3], [u8; 3]),
+    /// Monochrome image in shades of the given color, e.g., `{ tint: [255, 120, 0] }`
+    Tint([u8; 3]),
+}
+
+/// Clockwise rotation in degrees; only multiples of 90 are supported.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "u16")]
+pub(crate) enum Rotation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl TryFrom<u16> for Rotation {
+    type Error = String;
+
+    fn try_from(degrees: u16) -> Result<Self, Self::Error> {
+        match degrees {
+            90 => Ok(Self::Rotate90),
+            180 => Ok(Self::Rotate180),
+            270 => Ok(Self::Rotate270),
+            _ => Err(format!(
+                "unsupported rotation of {degrees} degrees, use 90, 180, or 270"
+            )),
+        }
+    }
+}
+
+/// `Content-Disposition` header the thumbnails of a preset are served with.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ContentDisposition {
+    /// Makes browsers download the thumbnail instead of showing it
+    #[serde(default)]
+    pub(crate) attachment: bool,
+    /// Name offered for the downloaded file, with the placeholders of the naming pattern, e.g.,
+    /// `{image_stem}_{width}x{height}`. The extension is always appended. Defaults to
+    /// `{image_stem}_{thumb_name}`.
+    pub(crate) filename: Option<String>,
+}
+
+/// Shape of the visible part of a thumbnail; everything outside is transparent.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Mask {
+    /// The largest circle that fits into the thumbnail, e.g., for avatars
+    Circle,
+    /// Rounded corners with the given radius in pixels
+    Rounded(f32),
+}
+
+/// Where to place the center of the thumbnail, if the edges of the image need to be cut off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Center {
+    /// (width, height) in percent (i.e., between 0 and 1)
+    Manual(f32, f32),
+    /// The region of the image with the highest entropy, i.e., the most detail.
+    Auto,
+}
+
+impl Default for Center {
+    fn default() -> Self {
+        Self::Manual(0.5, 0.5)
+    }
+}
+
+impl From<(f32, f32)> for Center {
+    fn from((width, height): (f32, f32)) -> Self {
+        Self::Manual(width, height)
+    }
+}
+
+/// Rectangle in pixels of the source image, e.g., a crop chosen by a user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Options of a single thumbnail creation call
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct CreateOptions<'a> {
+    pub(crate) force_override: bool,
+    /// Center used for all presets instead of their own
+    pub(crate) center: Option<Center>,
+    /// Part of the source image to create the thumbnails from
+    pub(crate) crop: Option<Rect>,
+    /// Store the thumbnails are written to instead of the instance's store
+    pub(crate) store: Option<&'a dyn ObjectStore>,
+    /// Names of the only presets to create, instead of all of them
+    pub(crate) presets: Option<&'a [&'a str]>,
+    /// Presets to create instead of the configured ones
+    pub(crate) settings: Option<&'a [Params]>,
+    /// Naming pattern used for all presets instead of their own
+    pub(crate) naming_pattern: Option<&'a str>,
+    /// Limits used instead of the instance's ones
+    pub(crate) timeouts: Option<Timeouts>,
+    /// Entity tag of the source image, stored with the thumbnails to detect unchanged sources
+    pub(crate) source_etag: Option<&'a str>,
+    /// Path of the source image in its store, stored with the thumbnails
+    pub(crate) source_path: Option<&'a str>,
+    /// Folders of the image that are mirrored below the destination directories
+    pub(crate) nested: Option<&'a Path>,
+}
+
+impl<'a> CreateOptions<'a> {
+    /// Options for the thumbnails of an image downloaded from the given path in the store, which
+    /// keep track of the image and the version they were created from.
+    pub(crate) fn for_image(file: &'a str, image: &'a ImageDetails, force_override: bool) -> Self {
+        Self {
+            force_override,
+            source_etag: image.e_tag.as_deref(),
+            source_path: Some(file),
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -39,5 +397,93 @@ pub(crate) struct ImageDetails {
     pub(crate) stem: String,
     pub(crate) format: ImageFormat,
     pub(crate) path: Path,
-    pub(crate) bytes: Vec<u8>,
+    pub(crate) bytes: Bytes,
+    /// Metadata and headers stored with the object
+    pub(crate) attributes: Attributes,
+    /// Width and height in pixels, only known for created thumbnails
+    pub(crate) dimensions: Option<(u32, u32)>,
+    /// Entity tag of the object, only known for downloaded images
+    pub(crate) e_tag: Option<String>,
+}
+
+/// Thumbnail that exists in the store.
+#[derive(Debug, Clone)]
+pub struct ThumbInfo {
+    /// Name of the preset the thumbnail was created with
+    pub preset: String,
+    /// Path, size, and modification time of the thumbnail
+    pub meta: ObjectMeta,
+}
+
+/// Outcome of creating the thumbnails of one image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThumbReport {
+    /// Path of the source image
+    pub image: Path,
+    /// Directory the thumbnails of the image are stored in
+    pub dest_dir: Path,
+    /// Whether the image was skipped because all of its thumbnails already exist, or, in
+    /// directory runs, because it is no image
+    pub skipped: bool,
+    /// Thumbnails that were created and stored, without those that already existed
+    pub thumbs: Vec<CreatedThumb>,
+    /// Hash of the contents of the image, if a naming pattern contains `{source_hash}`
+    pub source_hash: Option<String>,
+    /// Folders of the image that are mirrored below the destination directories
+    pub(crate) nested: Path,
+}
+
+/// Thumbnail that was created and stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatedThumb {
+    /// Path of the thumbnail in the store
+    pub path: Path,
+    pub format: ImageFormat,
+    /// Width and height in pixels
+    pub dimensions: (u32, u32),
+    /// Size of the encoded thumbnail in bytes
+    pub size: usize,
+}
+
+/// Accumulated outcome of replicating thumbnails to the secondary stores.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplicationSummary {
+    /// Number of thumbnail copies that were stored successfully on a replica
+    pub replicated: usize,
+    /// Number of additional attempts needed because a replica upload failed
+    pub retries: usize,
+    /// Thumbnails that could not be stored on one of the replicas, even after retrying
+    pub failed: Vec<Path>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContentDisposition, Mode, Params};
+
+    #[test]
+    fn fingerprint_only_covers_pixels() {
+        let params = Params::new("mini", (10, 10), Mode::Crop, 80);
+        let fingerprint = params.fingerprint();
+        let stored_differently = Params {
+            name: "small".to_string(),
+            dest_dir: Some("/cdn".to_string()),
+            naming_pattern: Some("/{image_stem}_{hash}".to_string()),
+            skip_smaller: true,
+            cache_control: Some("public".to_string()),
+            content_disposition: Some(ContentDisposition::default()),
+            ..params.clone()
+        };
+        assert_eq!(stored_differently.fingerprint(), fingerprint);
+
+        let sharper = Params {
+            sharpen: Some(0.5),
+            ..params.clone()
+        };
+        assert_ne!(sharper.fingerprint(), fingerprint);
+        let larger = Params {
+            size: (20, 20),
+            ..params
+        };
+        assert_ne!(larger.fingerprint(), fingerprint);
+    }
 }
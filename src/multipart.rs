@@ -0,0 +1,201 @@
+use object_store::path::Path;
+use object_store::{
+    Attributes, ObjectStore, PutMode, PutMultipartOpts, PutOptions, PutPayload, WriteMultipart,
+};
+
+use crate::checksum::insert_checksum;
+use crate::{ImageThumbs, ThumbsResult};
+
+/// Size of the parts of multipart uploads, the minimum most providers accept
+const PART_SIZE: usize = 5 * 1024 * 1024;
+/// Number of parts of one multipart upload that are uploaded at the same time
+const CONCURRENT_PARTS: usize = 4;
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Uploads thumbnails larger than `threshold` bytes in parts of 5 MiB using the multipart API
+    /// of the store, e.g., full-size previews of panoramas. A failed part is retried on its own
+    /// instead of restarting the whole upload.
+    ///
+    /// # Arguments
+    /// * `threshold` - size in bytes above which thumbnails are uploaded in parts.
+    pub fn with_multipart_threshold(mut self, threshold: usize) -> Self {
+        self.multipart_threshold = Some(threshold);
+        self
+    }
+
+    /// Stores the payload at the given path, in parts if it exceeds the multipart threshold.
+    ///
+    /// With [`PutMode::Create`], returns `false` instead of storing the payload if the object
+    /// already exists. Stores without conditional puts fall back to checking for the object
+    /// first. The MD5 of the payload is stored with it, see [`ImageThumbs::download`]. The
+    /// attributes are dropped for stores that cannot keep metadata, like the local file system.
+    pub(crate) async fn put_object(
+        &self,
+        store: &dyn ObjectStore,
+        path: &Path,
+        payload: PutPayload,
+        mut options: PutOptions,
+    ) -> ThumbsResult<bool> {
+        insert_checksum(&mut options.attributes, &payload);
+        loop {
+            let result = self
+                .retrying(|| self.put_object_with(store, path, payload.clone(), options.clone()))
+                .await;
+            match result {
+                Ok(()) => return Ok(true),
+                Err(object_store::Error::AlreadyExists { .. }) => return Ok(false),
+                Err(object_store::Error::NotImplemented) if !options.attributes.is_empty() => {
+                    options.attributes = Attributes::new();
+                }
+                Err(object_store::Error::NotImplemented)
+                    if matches!(options.mode, PutMode::Create) =>
+                {
+                    if self.retrying(|| store.head(path)).await.is_ok() {
+                        return Ok(false);
+                    }
+                    options.mode = PutMode::Overwrite;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    async fn put_object_with(
+        &self,
+        store: &dyn ObjectStore,
+        path: &Path,
+        payload: PutPayload,
+        options: PutOptions,
+    ) -> object_store::Result<()> {
+        let multipart = match self.multipart_threshold {
+            Some(threshold) => payload.content_length() > threshold,
+            None => false,
+        };
+        if !multipart {
+            store.put_opts(path, payload, options).await?;
+            return Ok(());
+        }
+        // multipart uploads cannot be conditional
+        if matches!(options.mode, PutMode::Create) && store.head(path).await.is_ok() {
+            return Err(object_store::Error::AlreadyExists {
+                path: path.to_string(),
+                source: "object exists".into(),
+            });
+        }
+
+        let options = PutMultipartOpts {
+            tags: options.tags,
+            attributes: options.attributes,
+        };
+        let upload = store.put_multipart_opts(path, options).await?;
+        let mut upload = WriteMultipart::new_with_chunk_size(upload, PART_SIZE);
+        for bytes in payload {
+            if let Err(e) = upload.wait_for_capacity(CONCURRENT_PARTS).await {
+                upload.abort().await?;
+                return Err(e);
+            }
+            upload.put(bytes);
+        }
+        upload.finish().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::ImageFormat;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::{ObjectStore, PutMode, PutOptions};
+
+    use crate::model::Params;
+    use crate::test_utils::png;
+    use crate::ImageThumbs;
+
+    #[tokio::test]
+    async fn upload_in_parts() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client =
+            ImageThumbs::from_parts(InMemory::new(), settings).with_multipart_threshold(16);
+        let bytes = png();
+        client
+            .create_thumbs_from_bytes(
+                bytes,
+                "/thumbs",
+                "penguin",
+                ImageFormat::Png,
+                true,
+                (0.5, 0.5),
+            )
+            .await
+            .unwrap();
+
+        let thumb = client
+            .client
+            .get(&Path::parse("thumbs/penguin_mini.png").unwrap())
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(
+            image::load_from_memory(&thumb)
+                .unwrap()
+                .to_rgb8()
+                .dimensions(),
+            (10, 5)
+        );
+
+        let path = Path::parse("raw.bin").unwrap();
+        let payload = vec![7; 64].into();
+        client
+            .put_object(&client.client, &path, payload, Default::default())
+            .await
+            .unwrap();
+        let bytes = client
+            .client
+            .get(&path)
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(bytes.as_ref(), [7; 64]);
+    }
+
+    #[tokio::test]
+    async fn keep_existent_objects() {
+        let path = Path::parse("raw.bin").unwrap();
+        let create = || PutOptions {
+            mode: PutMode::Create,
+            ..Default::default()
+        };
+        for threshold in [None, Some(16)] {
+            let mut client = ImageThumbs::from_parts(InMemory::new(), Vec::new());
+            client.multipart_threshold = threshold;
+            let stored = client
+                .put_object(&client.client, &path, vec![1; 64].into(), create())
+                .await
+                .unwrap();
+            assert!(stored);
+            let stored = client
+                .put_object(&client.client, &path, vec![2; 64].into(), create())
+                .await
+                .unwrap();
+            assert!(!stored);
+            let bytes = client
+                .client
+                .get(&path)
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap();
+            assert_eq!(bytes.as_ref(), [1; 64]);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use image::ImageFormat;
+use object_store::ObjectStore;
+
+use crate::image::{calculate_thumbnail, decode, encode, run_blocking};
+use crate::model::{Mode, Params};
+use crate::{ImageThumbs, ThumbsResult};
+
+/// Used if no preset with `mode: placeholder` is configured
+const DEFAULT_SIZE: (u32, u32) = (20, 20);
+const DEFAULT_QUALITY: u8 = 30;
+
+/// Tiny, blurred version of an image, meant to be shown while the actual image is loading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placeholder {
+    pub bytes: Vec<u8>,
+    pub format: ImageFormat,
+}
+
+impl Placeholder {
+    /// Returns the placeholder base64 encoded.
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(&self.bytes)
+    }
+
+    /// Returns the placeholder as a data URI that can be used directly as the `src` of an `<img>`.
+    pub fn to_data_uri(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.format.to_mime_type(),
+            self.to_base64()
+        )
+    }
+}
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Gets one image from the object storage and creates a placeholder for it, without uploading
+    /// it.
+    ///
+    /// The first preset with `mode: placeholder` is used. If there is none, the placeholder fits
+    /// within 20x20 pixels and is encoded with a quality of 30.
+    ///
+    /// # Arguments
+    /// * `file` - image to create the placeholder for.
+    pub async fn create_placeholder(&self, file: &str) -> ThumbsResult<Placeholder> {
+        let image = self.download_image(file).await?;
+        self.create_placeholder_from_bytes(image.bytes, image.format)
+            .await
+    }
+
+    /// Takes the raw bytes of an image and creates a placeholder for it, without uploading it.
+    ///
+    /// See [`ImageThumbs::create_placeholder`] for the used preset.
+    ///
+    /// # Arguments
+    /// * `bytes` - raw image bytes to create the placeholder for.
+    ///
+    /// * `format` - format of the input image. The placeholder will have the same type.
+    pub async fn create_placeholder_from_bytes(
+        &self,
+        bytes: impl Into<Bytes>,
+        format: ImageFormat,
+    ) -> ThumbsResult<Placeholder> {
+        let params = self
+            .presets()
+            .iter()
+            .find(|params| matches!(params.mode, Mode::Placeholder))
+            .cloned()
+            .unwrap_or_else(|| Params {
+                name: "placeholder".to_string(),
+                quality: DEFAULT_QUALITY,
+                size: DEFAULT_SIZE,
+                mode: Mode::Placeholder,
+                ..Default::default()
+            });
+
+        let bytes = bytes.into();
+        run_blocking(move || {
+            let source = decode(&bytes, format)?;
+            let thumbnail = calculate_thumbnail(&source.image, &params, None)?;
+            Ok(Placeholder {
+                bytes: encode(&thumbnail, format, params.quality, None)?,
+                format,
+            })
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, GenericImageView, ImageFormat};
+    use object_store::memory::InMemory;
+
+    use crate::ImageThumbs;
+
+    #[tokio::test]
+    async fn create_placeholder() {
+        let mut bytes = Vec::new();
+        DynamicImage::new_rgb8(400, 200)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Jpeg)
+            .unwrap();
+
+        let client = ImageThumbs::from_parts(InMemory::new(), Vec::new());
+        let placeholder = client
+            .create_placeholder_from_bytes(bytes, ImageFormat::Jpeg)
+            .await
+            .unwrap();
+
+        let image = image::load_from_memory(&placeholder.bytes).unwrap();
+        assert_eq!(image.dimensions(), (20, 10));
+        assert!(placeholder
+            .to_data_uri()
+            .starts_with("data:image/jpeg;base64,"));
+    }
+}
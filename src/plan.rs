@@ -0,0 +1,219 @@
+use object_store::path::Path;
+use object_store::ObjectStore;
+
+use crate::{DirOptions, Error, ImageThumbs, ThumbsResult};
+
+/// What [`ImageThumbs::create_thumbs_dir_with_options`] would do with one thumbnail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannedAction {
+    /// The thumbnail does not exist yet and would be created
+    Create,
+    /// The thumbnail already exists and would be kept
+    Skip,
+    /// The thumbnail already exists and would be replaced
+    Overwrite,
+}
+
+/// One thumbnail of a dry run, see [`ImageThumbs::plan_thumbs_dir`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedThumb {
+    /// Image the thumbnail is created from
+    pub image: Path,
+    /// Name of the preset
+    pub preset: String,
+    /// Path the thumbnail is stored at
+    pub path: Path,
+    pub action: PlannedAction,
+}
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Dry run of [`ImageThumbs::create_thumbs_dir_with_options`] with the same arguments:
+    /// returns which thumbnails would be created, skipped, or overwritten, without downloading
+    /// any image or writing to the storage.
+    ///
+    /// Objects that cannot be images, e.g., videos or files without an extension, are left out,
+    /// as the run skips them as well. Only [`DirOptions::progress`] and
+    /// [`DirOptions::checkpoint`] are ignored.
+    pub async fn plan_thumbs_dir(
+        &self,
+        directory: Option<&str>,
+        dest_dir: &str,
+        options: &DirOptions,
+    ) -> ThumbsResult<Vec<PlannedThumb>> {
+        let options = DirOptions {
+            progress: None,
+            ..options.clone()
+        };
+        let reports = self
+            .list_dir(&self.client, directory, dest_dir, &options)
+            .await?;
+        let mut plan = Vec::new();
+        for report in reports {
+            let thumbs = match self.thumb_paths(&report.image, &report.dest_dir, &report.nested) {
+                Ok(thumbs) => thumbs,
+                // fails for all images, so the run cannot be planned
+                Err(e @ Error::ContentDependentPath(_)) => return Err(e),
+                Err(_) => continue,
+            };
+            let unchanged =
+                options.skip_unchanged && self.is_unchanged(&self.client, &report).await?;
+            for (params, path) in thumbs {
+                let action = match self.retrying(|| self.client.head(&path)).await {
+                    Ok(_) if options.force_override && !unchanged => PlannedAction::Overwrite,
+                    Ok(_) => PlannedAction::Skip,
+                    Err(object_store::Error::NotFound { .. }) => PlannedAction::Create,
+                    Err(err) => Err(err)?,
+                };
+                plan.push(PlannedThumb {
+                    image: report.image.clone(),
+                    preset: params.name.clone(),
+                    path,
+                    action,
+                });
+            }
+        }
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    use crate::model::Params;
+    use crate::test_utils::{client_with_images, put_all};
+    use crate::{DirOptions, PlannedAction, PlannedThumb};
+
+    #[tokio::test]
+    async fn plan_thumbs_dir() {
+        let settings = vec![
+            Params {
+                name: "mini".to_string(),
+                size: (10, 10),
+                ..Default::default()
+            },
+            Params {
+                name: "standard".to_string(),
+                size: (20, 20),
+                ..Default::default()
+            },
+        ];
+        let client = client_with_images(settings, &["images/penguin.png"]).await;
+        client
+            .create_thumb("images/penguin.png", "mini", "/thumbs", false)
+            .await
+            .unwrap();
+
+        let actions = |plan: Vec<PlannedThumb>| {
+            plan.into_iter()
+                .map(|thumb| (thumb.preset, thumb.action))
+                .collect::<Vec<_>>()
+        };
+        let plan = client
+            .plan_thumbs_dir(Some("images"), "/thumbs", &DirOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            plan[0].path,
+            Path::parse("thumbs/penguin_mini.png").unwrap()
+        );
+        assert_eq!(
+            actions(plan),
+            [
+                ("mini".to_string(), PlannedAction::Skip),
+                ("standard".to_string(), PlannedAction::Create)
+            ]
+        );
+        let options = DirOptions {
+            force_override: true,
+            ..Default::default()
+        };
+        let plan = client
+            .plan_thumbs_dir(Some("images"), "/thumbs", &options)
+            .await
+            .unwrap();
+        assert_eq!(
+            actions(plan),
+            [
+                ("mini".to_string(), PlannedAction::Overwrite),
+                ("standard".to_string(), PlannedAction::Create)
+            ]
+        );
+
+        // nothing was created
+        assert!(client
+            .client
+            .head(&Path::parse("thumbs/penguin_standard.png").unwrap())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn plan_like_the_run() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = client_with_images(
+            settings,
+            &[
+                "images/penguin.png",
+                "images/penguin_raw.png",
+                "images/2024/puffin.png",
+            ],
+        )
+        .await;
+        put_all(
+            &client.client,
+            &["images/README", "images/movie.mp4"],
+            b"no image",
+        )
+        .await;
+
+        let options = DirOptions {
+            recursive: true,
+            exclude: vec!["*_raw.*".to_string()],
+            ..Default::default()
+        };
+        client
+            .create_thumbs_dir_with_options(Some("images"), "images/thumbs", &options)
+            .await
+            .unwrap();
+        let plan = client
+            .plan_thumbs_dir(Some("images"), "images/thumbs", &options)
+            .await
+            .unwrap();
+        let mut planned = plan
+            .into_iter()
+            .map(|thumb| (thumb.path.to_string(), thumb.action))
+            .collect::<Vec<_>>();
+        planned.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            planned,
+            [
+                (
+                    "images/thumbs/2024/puffin_mini.png".to_string(),
+                    PlannedAction::Skip
+                ),
+                (
+                    "images/thumbs/penguin_mini.png".to_string(),
+                    PlannedAction::Skip
+                ),
+            ]
+        );
+
+        // unchanged images are kept, even if thumbnails would be overridden otherwise
+        let options = DirOptions {
+            force_override: true,
+            skip_unchanged: true,
+            ..options
+        };
+        let plan = client
+            .plan_thumbs_dir(Some("images"), "images/thumbs", &options)
+            .await
+            .unwrap();
+        assert!(plan.iter().all(|thumb| thumb.action == PlannedAction::Skip));
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use config::{Config, ConfigError};
+use glob::Pattern;
+use image::ImageFormat;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use serde::Deserialize;
+
+use crate::defaults::inherit_defaults;
+use crate::model::Params;
+use crate::validate::problems;
+use crate::{Error, ImageHashes, ImageThumbs, ThumbsResult};
+
+/// Key of the named groups of presets in the config file
+const PROFILES_KEY: &str = "profiles";
+/// Key of the mapping from source paths to profiles in the config file
+const PREFIXES_KEY: &str = "prefixes";
+
+/// Presets by the name of their profile
+type Profiles = HashMap<String, Vec<Params>>;
+
+/// Profile that is applied to all images of which the path matches the pattern
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct PrefixProfile {
+    pattern: String,
+    profile: String,
+}
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Reads the named groups of presets from the `profiles` block of the config file, e.g., for
+    /// avatars, products, and banners served by one instance. A profile is applied instead of the
+    /// configured presets with [`ImageThumbs::create_thumbs_with_profile`].
+    ///
+    /// ```yaml
+    /// thumbs:
+    ///   - { name: standard, quality: 80, size: [ 640, 480 ], mode: fit }
+    /// profiles:
+    ///   avatars:
+    ///     - { name: small, quality: 80, size: [ 40, 40 ], mode: crop }
+    ///   banners:
+    ///     - { name: wide, quality: 80, size: [ 1200, 300 ], mode: crop, center: [ 0.5, 0.25 ] }
+    /// ```
+    ///
+    /// The presets of every profile inherit the `defaults` block like those in `thumbs`.
+    ///
+    /// An optional `prefixes` block applies a profile to all images of which the path matches a
+    /// glob pattern, e.g., in a [`ImageThumbs::create_thumbs_dir`] run over the whole bucket.
+    /// The first matching pattern wins; other images get the presets in `thumbs`.
+    ///
+    /// ```yaml
+    /// prefixes:
+    ///   - { pattern: "users/**", profile: avatars }
+    ///   - { pattern: "banners/*", profile: banners }
+    /// ```
+    ///
+    /// # Arguments
+    /// * `config` - Path to the config file from the crate root (the extension may be omitted),
+    ///   usually the one the instance was created from.
+    pub fn with_profiles(mut self, config: &str) -> ThumbsResult<Self> {
+        let (profiles, prefixes) = load_profiles(config::File::with_name(config))?;
+        self.profiles.extend(
+            profiles
+                .into_iter()
+                .map(|(name, settings)| (name, Arc::new(settings))),
+        );
+        self.prefix_profiles.extend(prefixes);
+        Ok(self)
+    }
+
+    /// Adds a named group of presets, see [`ImageThumbs::with_profiles`].
+    pub fn with_profile(mut self, name: &str, settings: Vec<Params>) -> Self {
+        self.profiles.insert(name.to_string(), Arc::new(settings));
+        self
+    }
+
+    /// Applies the profile to all images of which the path matches the glob pattern, e.g.,
+    /// `users/**`, see [`ImageThumbs::with_profiles`]. Patterns are checked in the order they
+    /// were added.
+    pub fn with_prefix_profile(mut self, pattern: &str, profile: &str) -> ThumbsResult<Self> {
+        self.prefix_profiles
+            .push((Pattern::new(pattern)?, profile.to_string()));
+        Ok(self)
+    }
+
+    /// Like [`ImageThumbs::create_thumbs`], but creates the presets of the given profile instead
+    /// of the configured ones.
+    ///
+    /// # Arguments
+    /// * `file` - image to create thumbnails for.
+    ///
+    /// * `dest_dir` - directory to store all created thumbnails.
+    ///   This directory will be checked for already existent thumbnails if `force_override` is false.
+    ///
+    /// * `force_override` - if `true` it will override already existent files with the same name.
+    ///   If false, it will preserve already existent files.
+    ///
+    /// * `profile` - name of the profile, see [`ImageThumbs::with_profiles`].
+    ///
+    /// Fails with [`Error::UnknownProfile`] if there is no profile with the given name.
+    pub async fn create_thumbs_with_profile(
+        &self,
+        file: &str,
+        dest_dir: &str,
+        force_override: bool,
+        profile: &str,
+    ) -> ThumbsResult<ImageHashes> {
+        let params = self.profile(profile)?;
+        self.create_thumbs_with_params(file, dest_dir, force_override, &params)
+            .await
+    }
+
+    /// Like [`ImageThumbs::create_thumbs_from_bytes`], but creates the presets of the given
+    /// profile instead of the configured ones.
+    ///
+    /// See [`ImageThumbs::create_thumbs_from_bytes`] and
+    /// [`ImageThumbs::create_thumbs_with_profile`] for the arguments.
+    pub async fn create_thumbs_from_bytes_with_profile(
+        &self,
+        bytes: impl Into<Bytes>,
+        dest_dir: &str,
+        image_name: &str,
+        format: ImageFormat,
+        force_override: bool,
+        profile: &str,
+    ) -> ThumbsResult<ImageHashes> {
+        let params = self.profile(profile)?;
+        self.create_thumbs_from_bytes_with_params(
+            bytes,
+            dest_dir,
+            image_name,
+            format,
+            force_override,
+            &params,
+        )
+        .await
+    }
+
+    /// Returns the presets of the profile with the given name.
+    pub(crate) fn profile(&self, name: &str) -> ThumbsResult<Arc<Vec<Params>>> {
+        self.profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::UnknownProfile(name.to_string()))
+    }
+
+    /// Returns the presets for the given source image, i.e., those of the profile of the first
+    /// matching prefix pattern, or the configured ones.
+    pub(crate) fn presets_for(&self, image: &Path) -> ThumbsResult<Arc<Vec<Params>>> {
+        match self
+            .prefix_profiles
+            .iter()
+            .find(|(pattern, _)| pattern.matches(image.as_ref()))
+        {
+            Some((_, profile)) => self.profile(profile),
+            None => Ok(self.presets()),
+        }
+    }
+}
+
+/// Reads and validates the profiles and the prefix patterns they are applied to from the given
+/// configuration source.
+fn load_profiles(
+    source: impl config::Source + Send + Sync + 'static,
+) -> ThumbsResult<(Profiles, Vec<(Pattern, String)>)> {
+    let mut builder = Config::builder().add_source(source);
+    let config = builder.clone().build()?;
+    let mut names: Vec<String> = config.get_table(PROFILES_KEY)?.into_keys().collect();
+    names.sort();
+    for name in &names {
+        let key = format!("{PROFILES_KEY}.{name}");
+        builder = inherit_defaults(builder, &config, &key, &config.get_array(&key)?)?;
+    }
+    let profiles: Profiles = builder.build()?.get(PROFILES_KEY)?;
+    let prefixes: Vec<PrefixProfile> = match config.get(PREFIXES_KEY) {
+        Ok(prefixes) => prefixes,
+        Err(ConfigError::NotFound(_)) => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut problems: Vec<String> = names
+        .iter()
+        .flat_map(|name| problems(&format!("{PROFILES_KEY}.{name}"), &profiles[name]))
+        .collect();
+    let mut patterns = Vec::with_capacity(prefixes.len());
+    for (index, prefix) in prefixes.into_iter().enumerate() {
+        match Pattern::new(&prefix.pattern) {
+            Ok(pattern) => patterns.push((pattern, prefix.profile.clone())),
+            Err(e) => problems.push(format!("{PREFIXES_KEY}[{index}].pattern: {e}")),
+        }
+        if !profiles.contains_key(&prefix.profile) {
+            problems.push(format!(
+                "{PREFIXES_KEY}[{index}].profile: `{}` is not defined in {PROFILES_KEY}",
+                prefix.profile
+            ));
+        }
+    }
+    if problems.is_empty() {
+        Ok((profiles, patterns))
+    } else {
+        Err(Error::InvalidSettings(problems))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{File, FileFormat};
+    use image::ImageFormat;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    use super::load_profiles;
+    use crate::model::{Mode, Params, Rect};
+    use crate::test_utils::{png, put_all};
+    use crate::{Error, ImageThumbs};
+
+    #[test]
+    fn load_profiles_with_defaults() {
+        let config = "defaults: { quality: 80, mode: crop }
+thumbs: []
+profiles:
+  avatars:
+    - { name: small, size: [40, 40] }
+  banners:
+    - { name: wide, size: [1200, 300], mode: fit }";
+        let (profiles, _) = load_profiles(File::from_str(config, FileFormat::Yaml)).unwrap();
+        assert_eq!(profiles["avatars"][0].quality, 80);
+        assert!(matches!(profiles["avatars"][0].mode, Mode::Crop));
+        assert!(matches!(profiles["banners"][0].mode, Mode::Fit));
+
+        let config = "profiles:
+  avatars:
+    - { name: small, quality: 0, size: [40, 40], mode: crop }
+prefixes:
+  - { pattern: \"users/**\", profile: avatar }";
+        let Err(Error::InvalidSettings(problems)) =
+            load_profiles(File::from_str(config, FileFormat::Yaml))
+        else {
+            panic!("profile is invalid");
+        };
+        assert_eq!(
+            problems,
+            [
+                "profiles.avatars[0].quality: must be between 1 and 100, got 0",
+                "prefixes[0].profile: `avatar` is not defined in profiles",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn create_thumbs_with_profile() {
+        let client = ImageThumbs::from_parts(
+            InMemory::new(),
+            vec![Params::new("standard", (20, 20), Mode::Fit, 80)],
+        )
+        .with_profile(
+            "avatars",
+            vec![Params::new("small", (10, 10), Mode::Crop, 80)],
+        );
+        let bytes = png();
+        client
+            .create_thumbs_from_bytes_with_profile(
+                bytes,
+                "thumbs",
+                "penguin",
+                ImageFormat::Png,
+                false,
+                "avatars",
+            )
+            .await
+            .unwrap();
+
+        let thumbs = ImageThumbs::<InMemory>::list_folder(
+            &client.client,
+            Some(&Path::parse("thumbs").unwrap()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(thumbs, [Path::parse("thumbs/penguin_small.png").unwrap()]);
+        assert!(client
+            .client
+            .head(&Path::parse("thumbs/penguin_standard.png").unwrap())
+            .await
+            .is_err());
+        assert!(matches!(
+            client
+                .create_thumbs_with_profile("penguin.png", "thumbs", false, "products")
+                .await,
+            Err(Error::UnknownProfile(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn use_prefix_profiles() {
+        let client = ImageThumbs::from_parts(
+            InMemory::new(),
+            vec![Params::new("standard", (20, 20), Mode::Fit, 80)],
+        )
+        .with_profile(
+            "avatars",
+            vec![Params::new("small", (10, 10), Mode::Crop, 80)],
+        )
+        .with_prefix_profile("users/**", "avatars")
+        .unwrap();
+        let bytes = png();
+        put_all(
+            &client.client,
+            &["users/alice.png", "users/bob.png", "users/carol.png"],
+            &bytes,
+        )
+        .await;
+
+        assert_eq!(
+            client
+                .missing_thumbs("users/alice.png", "thumbs")
+                .await
+                .unwrap(),
+            ["small"]
+        );
+        client
+            .create_thumb("users/alice.png", "small", "thumbs", false)
+            .await
+            .unwrap();
+        assert!(client
+            .has_all_thumbs("users/alice.png", "thumbs")
+            .await
+            .unwrap());
+        let (format, _) = client
+            .get_or_create_thumb("users/bob.png", "small", "thumbs")
+            .await
+            .unwrap();
+        assert_eq!(format, ImageFormat::Png);
+        assert!(matches!(
+            client
+                .create_thumb("users/alice.png", "standard", "thumbs", false)
+                .await,
+            Err(Error::UnknownPreset(_))
+        ));
+
+        let carol = "users/carol.png";
+        client
+            .create_thumbs_man_center(carol, "centered", false, (0.5, 0.5))
+            .await
+            .unwrap();
+        let crop = Rect {
+            x: 0,
+            y: 0,
+            width: 20,
+            height: 20,
+        };
+        client
+            .create_thumbs_cropped(carol, "cropped", false, crop)
+            .await
+            .unwrap();
+        client
+            .create_thumbs_with_naming_pattern(
+                carol,
+                "renamed",
+                false,
+                "/{image_stem}_{thumb_name}",
+            )
+            .await
+            .unwrap();
+        for dest_dir in ["centered", "cropped", "renamed"] {
+            assert!(
+                client.has_all_thumbs(carol, dest_dir).await.unwrap(),
+                "{dest_dir}"
+            );
+        }
+
+        client
+            .client
+            .delete(&Path::parse("users/bob.png").unwrap())
+            .await
+            .unwrap();
+        let purged = client.purge_orphans(Some("users"), "thumbs").await.unwrap();
+        assert_eq!(purged, [Path::parse("thumbs/bob_small.png").unwrap()]);
+    }
+}
@@ -0,0 +1,152 @@
+use object_store::{Attribute, Attributes};
+
+use crate::model::Params;
+
+/// Name of the object metadata entry the path of the source image is stored in
+pub(crate) const SOURCE_PATH_METADATA: &str = "source_path";
+/// Name of the object metadata entry the name of the preset is stored in
+pub(crate) const PRESET_NAME_METADATA: &str = "preset_name";
+/// Name of the object metadata entry the name and version of this crate are stored in
+pub(crate) const GENERATOR_METADATA: &str = "generator";
+/// Names of the object metadata entries the dimensions of the thumbnail are stored in
+pub(crate) const WIDTH_METADATA: &str = "width";
+pub(crate) const HEIGHT_METADATA: &str = "height";
+
+/// Adds how the thumbnail was created to the attributes it is stored with, so that other systems
+/// can tell where it comes from without decoding it.
+pub(crate) fn insert_provenance(
+    attributes: &mut Attributes,
+    params: &Params,
+    (width, height): (u32, u32),
+) {
+    let metadata = [
+        (PRESET_NAME_METADATA, params.name.clone()),
+        (
+            GENERATOR_METADATA,
+            concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")).to_string(),
+        ),
+        (WIDTH_METADATA, width.to_string()),
+        (HEIGHT_METADATA, height.to_string()),
+    ];
+    for (key, value) in metadata {
+        attributes.insert(Attribute::Metadata(key.into()), value.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::{Attribute, ObjectStore};
+
+    use super::{GENERATOR_METADATA, HEIGHT_METADATA, PRESET_NAME_METADATA, SOURCE_PATH_METADATA};
+    use crate::dir::SOURCE_ETAG_METADATA;
+    use crate::model::{Mode, Params, Rect};
+    use crate::test_utils::{client_with_images, png};
+    use crate::ImageThumbs;
+
+    #[tokio::test]
+    async fn store_provenance() {
+        let settings = vec![Params::new("standard", (20, 20), Mode::Fit, 80)];
+        let client = client_with_images(settings, &["images/penguin.png"]).await;
+        client
+            .create_thumbs("images/penguin.png", "thumbs", false)
+            .await
+            .unwrap();
+
+        let attributes = client
+            .client
+            .get(&Path::parse("thumbs/penguin_standard.png").unwrap())
+            .await
+            .unwrap()
+            .attributes;
+        let metadata = |key: &str| {
+            attributes
+                .get(&Attribute::Metadata(key.to_string().into()))
+                .map(|value| value.to_string())
+        };
+        assert_eq!(
+            metadata(SOURCE_PATH_METADATA).unwrap(),
+            "images/penguin.png"
+        );
+        assert!(metadata(SOURCE_ETAG_METADATA).is_some());
+        assert_eq!(metadata(PRESET_NAME_METADATA).unwrap(), "standard");
+        assert_eq!(metadata(HEIGHT_METADATA).unwrap(), "10");
+        assert!(metadata(GENERATOR_METADATA)
+            .unwrap()
+            .starts_with("image_thumbs "));
+    }
+
+    #[tokio::test]
+    async fn store_source_of_every_call() {
+        let settings = vec![Params::new("standard", (20, 20), Mode::Fit, 80)];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings.clone());
+        let bytes = png();
+        let image = "images/penguin.png";
+        client
+            .client
+            .put(&Path::parse(image).unwrap(), bytes.into())
+            .await
+            .unwrap();
+        let crop = Rect {
+            x: 0,
+            y: 0,
+            width: 20,
+            height: 20,
+        };
+        client
+            .create_thumbs_man_center(image, "centered", false, (0.5, 0.5))
+            .await
+            .unwrap();
+        client
+            .create_thumbs_cropped(image, "cropped", false, crop)
+            .await
+            .unwrap();
+        client
+            .create_thumbs_with_params(image, "custom", false, &settings)
+            .await
+            .unwrap();
+        client
+            .create_thumbs_with_naming_pattern(
+                image,
+                "renamed",
+                false,
+                "/{image_stem}_{thumb_name}",
+            )
+            .await
+            .unwrap();
+        client
+            .create_thumb(image, "standard", "single", false)
+            .await
+            .unwrap();
+        client
+            .get_or_create_thumb(image, "standard", "on_demand")
+            .await
+            .unwrap();
+
+        for dest_dir in [
+            "centered",
+            "cropped",
+            "custom",
+            "renamed",
+            "single",
+            "on_demand",
+        ] {
+            let path = Path::parse(format!("{dest_dir}/penguin_standard.png")).unwrap();
+            let attributes = client.client.get(&path).await.unwrap().attributes;
+            assert_eq!(
+                attributes
+                    .get(&Attribute::Metadata(SOURCE_PATH_METADATA.into()))
+                    .map(|value| value.to_string()),
+                Some(image.to_string()),
+                "{dest_dir}"
+            );
+            assert!(
+                attributes
+                    .get(&Attribute::Metadata(SOURCE_ETAG_METADATA.into()))
+                    .is_some(),
+                "{dest_dir}"
+            );
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use object_store::ObjectStore;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+
+use crate::ImageThumbs;
+
+/// Limits of the requests to the store, e.g., so that a huge backfill does not trip the rate
+/// limits of the provider or starve other traffic on the same bucket. Unset limits do not
+/// restrict the requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RateLimit {
+    /// Maximum number of requests started per second
+    pub requests_per_second: Option<f64>,
+    /// Maximum number of requests in flight at the same time
+    pub concurrent_requests: Option<usize>,
+}
+
+/// Enforces a [`RateLimit`] across all requests of one instance.
+#[derive(Debug, Default)]
+pub(crate) struct Limiter {
+    interval: Option<Duration>,
+    next_start: Mutex<Option<Instant>>,
+    concurrent: Option<Semaphore>,
+}
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Limits the requests for downloading images, checking for existent thumbnails, and
+    /// uploading thumbnails. Retried requests count as new requests.
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.limiter = Limiter::new(limit);
+        self
+    }
+}
+
+impl Limiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            interval: limit
+                .requests_per_second
+                .filter(|&requests| requests > 0.0)
+                .map(|requests| Duration::from_secs_f64(1.0 / requests)),
+            next_start: Mutex::new(None),
+            concurrent: limit
+                .concurrent_requests
+                .filter(|&requests| requests > 0)
+                .map(Semaphore::new),
+        }
+    }
+
+    /// Waits until another request may be started. The returned permit must be held until the
+    /// request is finished.
+    pub(crate) async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        if let Some(interval) = self.interval {
+            let start = {
+                let mut next_start = self.next_start.lock().expect("rate limiter lock poisoned");
+                let start = next_start.map_or(Instant::now(), |next| next.max(Instant::now()));
+                *next_start = Some(start + interval);
+                start
+            };
+            tokio::time::sleep_until(start).await;
+        }
+        match &self.concurrent {
+            Some(semaphore) => Some(
+                semaphore
+                    .acquire()
+                    .await
+                    .expect("rate limiter semaphore is never closed"),
+            ),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::{timeout, Instant};
+
+    use super::{Limiter, RateLimit};
+
+    #[tokio::test]
+    async fn limit_requests() {
+        let limiter = Limiter::new(RateLimit {
+            requests_per_second: Some(20.0),
+            ..Default::default()
+        });
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        let limiter = Limiter::new(RateLimit {
+            concurrent_requests: Some(1),
+            ..Default::default()
+        });
+        let permit = limiter.acquire().await;
+        assert!(timeout(Duration::from_millis(10), limiter.acquire())
+            .await
+            .is_err());
+        drop(permit);
+        assert!(timeout(Duration::from_millis(10), limiter.acquire())
+            .await
+            .is_ok());
+    }
+}
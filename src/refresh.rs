@@ -0,0 +1,232 @@
+use std::io::Cursor;
+
+use image::{guess_format, ImageError, ImageReader};
+use object_store::path::Path;
+use object_store::{Attribute, GetOptions, GetRange, ObjectStore};
+
+use crate::image::oriented_dimensions;
+use crate::model::{CreateOptions, Params};
+use crate::{ImageThumbs, ThumbsResult};
+
+/// Name of the object metadata entry the fingerprint of the preset a thumbnail was created with is
+/// stored in
+pub(crate) const PRESET_METADATA: &str = "preset_fingerprint";
+/// Number of bytes of an image that are read to find its dimensions
+const HEADER_LENGTH: usize = 64 * 1024;
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Recreates the thumbnails of all images on one object storage level that are missing or
+    /// were created with a different version of their preset, e.g., after its size was changed.
+    ///
+    /// Thumbnails are compared by the fingerprint of their preset that is stored as metadata,
+    /// which only covers the options that change the pixels, e.g., not the `cache_control`.
+    /// Stores that cannot keep metadata, or thumbnails created before this check existed, are
+    /// compared by their dimensions instead, which does not detect changes of, e.g., the quality.
+    ///
+    /// # Arguments
+    /// * `directory` - directory of the images to check.
+    ///
+    /// * `dest_dir` - directory the thumbnails are stored in.
+    ///
+    /// Returns the number of recreated thumbnails.
+    pub async fn refresh_thumbs_dir(
+        &self,
+        directory: Option<&str>,
+        dest_dir: &str,
+    ) -> ThumbsResult<usize> {
+        let prefix = match directory {
+            Some(p) => Some(Path::parse(p)?),
+            None => None,
+        };
+        let images = Self::list_folder(self.source(), prefix.as_ref()).await?;
+        let mut refreshed = 0;
+        for image in images {
+            let dest_path = Self::resolve_dest_dir(dest_dir, image.as_ref())?;
+            let Ok(thumbs) = self.thumb_paths(&image, &dest_path, &Path::default()) else {
+                continue; // not an image
+            };
+            let mut stale = Vec::new();
+            let mut source_dimensions = None;
+            for (params, path) in &thumbs {
+                if !self.is_stale(params, path).await? {
+                    continue;
+                }
+                if params.skip_smaller {
+                    let dimensions = match source_dimensions {
+                        Some(dimensions) => dimensions,
+                        None => *source_dimensions.insert(self.source_dimensions(&image).await?),
+                    };
+                    if params.covers(dimensions) {
+                        continue; // the thumbnail is skipped on purpose
+                    }
+                }
+                stale.push(params.name.as_str());
+            }
+            if stale.is_empty() {
+                continue;
+            }
+
+            let source = self.download_image(image.as_ref()).await?;
+            let options = CreateOptions {
+                force_override: true,
+                presets: Some(&stale),
+                ..Default::default()
+            };
+            self.create_thumbs_with_options(
+                source.bytes,
+                dest_path.as_ref(),
+                &source.stem,
+                source.format,
+                options,
+            )
+            .await?;
+            refreshed += stale.len();
+        }
+        Ok(refreshed)
+    }
+
+    /// Dimensions of the image, read from its first bytes if possible, so that it is only
+    /// downloaded entirely if its headers are exceptionally large.
+    async fn source_dimensions(&self, image: &Path) -> ThumbsResult<(u32, u32)> {
+        let header = self
+            .retrying(|| async {
+                let options = GetOptions {
+                    range: Some(GetRange::Bounded(0..HEADER_LENGTH)),
+                    ..Default::default()
+                };
+                self.source().get_opts(image, options).await?.bytes().await
+            })
+            .await?;
+        let format = guess_format(&header)?;
+        match oriented_dimensions(&header, format) {
+            Ok(dimensions) => Ok(dimensions),
+            Err(_) => {
+                let source = self.download_image(image.as_ref()).await?;
+                oriented_dimensions(&source.bytes, source.format)
+            }
+        }
+    }
+
+    async fn is_stale(&self, params: &Params, path: &Path) -> ThumbsResult<bool> {
+        let head = || GetOptions {
+            head: true,
+            ..Default::default()
+        };
+        let result = match self.retrying(|| self.client.get_opts(path, head())).await {
+            Ok(result) => result,
+            Err(object_store::Error::NotFound { .. }) => return Ok(true),
+            Err(err) => Err(err)?,
+        };
+        if let Some(fingerprint) = result
+            .attributes
+            .get(&Attribute::Metadata(PRESET_METADATA.into()))
+        {
+            return Ok(fingerprint.as_ref() != params.fingerprint());
+        }
+
+        let bytes = self
+            .retrying(|| async { self.client.get(path).await?.bytes().await })
+            .await?;
+        let dimensions = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .map_err(ImageError::IoError)?
+            .into_dimensions()?;
+        Ok(!params.matches_size(dimensions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::GenericImageView;
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    use crate::model::{Mode, Params};
+    use crate::test_utils::client_with_images;
+
+    #[tokio::test]
+    async fn refresh_changed_presets() {
+        let settings = vec![
+            Params {
+                name: "mini".to_string(),
+                size: (10, 10),
+                ..Default::default()
+            },
+            Params {
+                name: "standard".to_string(),
+                size: (20, 20),
+                ..Default::default()
+            },
+            // never stored for the image, which is smaller
+            Params {
+                name: "large".to_string(),
+                size: (100, 100),
+                skip_smaller: true,
+                ..Default::default()
+            },
+        ];
+        let client = client_with_images(settings, &["images/penguin.png"]).await;
+        client
+            .create_thumbs_dir(Some("images"), "/thumbs", false)
+            .await
+            .unwrap();
+        assert_eq!(
+            client
+                .refresh_thumbs_dir(Some("images"), "/thumbs")
+                .await
+                .unwrap(),
+            0
+        );
+
+        // headers do not change the pixels
+        let mut settings = client.presets().to_vec();
+        settings[0].cache_control = Some("public, max-age=60".to_string());
+        client.replace_settings(settings.clone());
+        assert_eq!(
+            client
+                .refresh_thumbs_dir(Some("images"), "/thumbs")
+                .await
+                .unwrap(),
+            0
+        );
+
+        settings[1].size = (30, 30);
+        client.replace_settings(settings);
+        assert_eq!(
+            client
+                .refresh_thumbs_dir(Some("images"), "/thumbs")
+                .await
+                .unwrap(),
+            1
+        );
+        let thumb = client
+            .client
+            .get(&Path::parse("thumbs/penguin_standard.png").unwrap())
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        let thumb = image::load_from_memory(&thumb).unwrap();
+        assert_eq!(thumb.dimensions(), (30, 15));
+    }
+
+    #[test]
+    fn matches_size() {
+        let fit = Params {
+            size: (30, 30),
+            ..Default::default()
+        };
+        assert!(fit.matches_size((30, 15)));
+        assert!(fit.matches_size((10, 30)));
+        assert!(!fit.matches_size((20, 10)));
+
+        let crop = Params {
+            size: (30, 30),
+            mode: Mode::Crop,
+            ..Default::default()
+        };
+        assert!(crop.matches_size((30, 30)));
+        assert!(!crop.matches_size((30, 15)));
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use object_store::ObjectStore;
+
+use crate::model::Params;
+use crate::{load_settings, ImageThumbs, ThumbsResult};
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Reads the settings from the config file again and uses them for all following calls, so
+    /// that a long-running service picks up changed presets without a restart. Calls that are
+    /// already running finish with the previous settings.
+    ///
+    /// The previous settings are kept if the file cannot be read or is invalid.
+    ///
+    /// # Arguments
+    /// * `config` - Path to the config file from the crate root (the extension may be omitted),
+    ///   see [`ImageThumbs::new`].
+    pub fn reload_settings(&self, config: &str) -> ThumbsResult<()> {
+        let settings = load_settings(config::File::with_name(config))?;
+        self.replace_settings(settings);
+        Ok(())
+    }
+
+    /// Uses the given presets for all following calls, e.g., after they changed in the
+    /// configuration system of the service. Calls that are already running finish with the
+    /// previous settings.
+    pub fn replace_settings(&self, settings: Vec<Params>) {
+        *self.settings.write().expect("settings lock poisoned") = Arc::new(settings);
+    }
+
+    /// Current presets. The lock is only held while cloning the pointer, so that replacing the
+    /// settings never waits for running calls.
+    pub(crate) fn presets(&self) -> Arc<Vec<Params>> {
+        self.settings
+            .read()
+            .expect("settings lock poisoned")
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use object_store::memory::InMemory;
+
+    use crate::model::{Mode, Params};
+    use crate::{Error, ImageThumbs};
+
+    #[test]
+    fn reload_settings() {
+        let client = ImageThumbs::from_parts(
+            InMemory::new(),
+            vec![Params::new("mini", (10, 10), Mode::Fit, 80)],
+        );
+        let previous = client.presets();
+
+        let path =
+            std::env::temp_dir().join(format!("image_thumbs_reload_{}.yaml", std::process::id()));
+        std::fs::write(
+            &path,
+            "thumbs:\n  - { name: standard, quality: 80, size: [640, 480], mode: fit }",
+        )
+        .unwrap();
+        client.reload_settings(path.to_str().unwrap()).unwrap();
+        assert_eq!(client.presets()[0].name, "standard");
+        assert_eq!(previous[0].name, "mini");
+
+        std::fs::write(&path, "thumbs:\n  - { name: standard, quality: 0 }").unwrap();
+        assert!(matches!(
+            client.reload_settings(path.to_str().unwrap()),
+            Err(Error::Config(_))
+        ));
+        assert_eq!(client.presets()[0].name, "standard");
+        std::fs::remove_file(path).unwrap();
+    }
+}
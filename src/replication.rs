@@ -0,0 +1,172 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::join_all;
+use object_store::path::Path;
+use object_store::{ObjectStore, PutOptions, PutPayload};
+use tokio::sync::watch;
+
+use crate::checksum::insert_checksum;
+use crate::model::ReplicationSummary;
+use crate::ImageThumbs;
+
+/// How often an upload to a replica is attempted before it is counted as failed
+const REPLICATION_ATTEMPTS: usize = 3;
+/// Delay before the first retry; doubled for every following retry
+const REPLICATION_BACKOFF: Duration = Duration::from_millis(100);
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Adds a secondary store (e.g., a bucket in another region) to which every created thumbnail
+    /// is copied after it was successfully uploaded to the primary store.
+    ///
+    /// The copies are uploaded in the background. Failing replica uploads are retried and never
+    /// fail the thumbnail creation itself. Their outcome can be inspected with
+    /// [`ImageThumbs::replication_summary`].
+    pub fn with_replica(mut self, replica: Arc<dyn ObjectStore>) -> Self {
+        self.replicas.push(replica);
+        self
+    }
+
+    /// Returns the accumulated outcome of all replica uploads of this instance that finished so
+    /// far. See [`ImageThumbs::wait_for_replication`] to include those still running.
+    pub fn replication_summary(&self) -> ReplicationSummary {
+        self.replication_summary
+            .lock()
+            .expect("replication summary lock poisoned")
+            .clone()
+    }
+
+    /// Waits until no replica uploads are running anymore, e.g., before shutting down or
+    /// inspecting the [`ImageThumbs::replication_summary`]. Uploads started while waiting are
+    /// waited for as well. Can be called concurrently.
+    pub async fn wait_for_replication(&self) {
+        let mut pending = self.replication_pending.subscribe();
+        // cannot fail, as the sender is kept by the instance
+        let _ = pending.wait_for(|pending| *pending == 0).await;
+    }
+
+    /// Copies one thumbnail to all replicas concurrently in the background and records the
+    /// outcome, so that slow replicas do not delay the thumbnail creation.
+    pub(crate) fn replicate(&self, path: &Path, payload: PutPayload, mut options: PutOptions) {
+        if self.replicas.is_empty() {
+            return;
+        }
+        insert_checksum(&mut options.attributes, &payload);
+
+        let replicas = self.replicas.clone();
+        let total = Arc::clone(&self.replication_summary);
+        let path = path.clone();
+        let pending = PendingUpload::new(&self.replication_pending);
+        tokio::spawn(async move {
+            let uploads = replicas.iter().map(|replica| {
+                put_with_retry(replica.as_ref(), &path, payload.clone(), options.clone())
+            });
+            let mut summary = ReplicationSummary::default();
+            for result in join_all(uploads).await {
+                match result {
+                    Ok(retries) => {
+                        summary.replicated += 1;
+                        summary.retries += retries;
+                    }
+                    Err(retries) => {
+                        summary.retries += retries;
+                        summary.failed.push(path.clone());
+                    }
+                }
+            }
+
+            let mut total = total.lock().expect("replication summary lock poisoned");
+            total.replicated += summary.replicated;
+            total.retries += summary.retries;
+            total.failed.append(&mut summary.failed);
+            // only finished once it is counted in the summary
+            drop(total);
+            drop(pending);
+        });
+    }
+}
+
+/// Counts a replica upload as pending until it is dropped, even if the upload panics.
+struct PendingUpload(Arc<watch::Sender<usize>>);
+
+impl PendingUpload {
+    fn new(pending: &Arc<watch::Sender<usize>>) -> Self {
+        pending.send_modify(|pending| *pending += 1);
+        Self(Arc::clone(pending))
+    }
+}
+
+impl Drop for PendingUpload {
+    fn drop(&mut self) {
+        self.0.send_modify(|pending| *pending -= 1);
+    }
+}
+
+/// Returns the number of retries needed, both when the upload eventually succeeded and when it
+/// failed.
+async fn put_with_retry(
+    store: &dyn ObjectStore,
+    path: &Path,
+    payload: PutPayload,
+    options: PutOptions,
+) -> Result<usize, usize> {
+    let mut backoff = REPLICATION_BACKOFF;
+    for attempt in 0..REPLICATION_ATTEMPTS {
+        if store
+            .put_opts(path, payload.clone(), options.clone())
+            .await
+            .is_ok()
+        {
+            return Ok(attempt);
+        }
+        if attempt + 1 < REPLICATION_ATTEMPTS {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+    Err(REPLICATION_ATTEMPTS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use image::ImageFormat;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::{Attributes, ObjectStore};
+
+    use crate::model::ImageDetails;
+    use crate::ImageThumbs;
+
+    #[tokio::test]
+    async fn replicate_thumbs() {
+        let replica = Arc::new(InMemory::new());
+        let client =
+            ImageThumbs::from_parts(InMemory::new(), Vec::new()).with_replica(replica.clone());
+        let thumb = ImageDetails {
+            stem: "penguin_standard".to_string(),
+            format: ImageFormat::Png,
+            path: Path::parse("/thumbs").unwrap(),
+            bytes: vec![1, 2, 3].into(),
+            attributes: Attributes::new(),
+            dimensions: None,
+            e_tag: None,
+        };
+        client
+            .upload_thumbs(&client.client, vec![thumb], true)
+            .await
+            .unwrap();
+        // waiting concurrently does not keep one of the callers waiting
+        tokio::join!(client.wait_for_replication(), client.wait_for_replication());
+
+        let path = Path::parse("thumbs/penguin_standard.png").unwrap();
+        client.client.head(&path).await.unwrap();
+        replica.head(&path).await.unwrap();
+
+        let summary = client.replication_summary();
+        assert_eq!(summary.replicated, 1);
+        assert_eq!(summary.retries, 0);
+        assert!(summary.failed.is_empty());
+    }
+}
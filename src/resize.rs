@@ -0,0 +1,113 @@
+use image::DynamicImage;
+
+use crate::ThumbsResult;
+
+/// Scales the image to the largest size that fits within the bounds, preserving its aspect ratio.
+pub(crate) fn resize_fit(image: &DynamicImage, bounds: (u32, u32)) -> ThumbsResult<DynamicImage> {
+    #[cfg(feature = "fast_resize")]
+    {
+        fast::resize(
+            image,
+            fit_dimensions((image.width(), image.height()), bounds),
+            false,
+        )
+    }
+    #[cfg(not(feature = "fast_resize"))]
+    {
+        Ok(image.thumbnail(bounds.0, bounds.1))
+    }
+}
+
+/// Scales the image to fill the given size, cutting off the edges that do not fit around the
+/// center.
+pub(crate) fn resize_fill(image: &DynamicImage, size: (u32, u32)) -> ThumbsResult<DynamicImage> {
+    #[cfg(feature = "fast_resize")]
+    {
+        fast::resize(image, size, true)
+    }
+    #[cfg(not(feature = "fast_resize"))]
+    {
+        Ok(image.resize_to_fill(size.0, size.1, image::imageops::FilterType::Nearest))
+    }
+}
+
+/// Scales the image to exactly the given size, ignoring its aspect ratio.
+pub(crate) fn resize_exact(image: &DynamicImage, size: (u32, u32)) -> ThumbsResult<DynamicImage> {
+    #[cfg(feature = "fast_resize")]
+    {
+        fast::resize(image, size, false)
+    }
+    #[cfg(not(feature = "fast_resize"))]
+    {
+        Ok(image.resize_exact(size.0, size.1, image::imageops::FilterType::Triangle))
+    }
+}
+
+/// Same dimensions as computed by [`DynamicImage::thumbnail`]
+#[cfg_attr(not(feature = "fast_resize"), allow(dead_code))]
+fn fit_dimensions((width, height): (u32, u32), bounds: (u32, u32)) -> (u32, u32) {
+    let ratio = (bounds.0 as f64 / width as f64).min(bounds.1 as f64 / height as f64);
+    (
+        ((width as f64 * ratio).round() as u32).max(1),
+        ((height as f64 * ratio).round() as u32).max(1),
+    )
+}
+
+#[cfg(feature = "fast_resize")]
+mod fast {
+    use fast_image_resize::{FilterType, ResizeAlg, ResizeOptions, Resizer};
+    use image::DynamicImage;
+
+    use crate::{Error, ThumbsResult};
+
+    /// Resamples the image with SIMD-accelerated convolution. If `fill` is set, the source is
+    /// cropped around its center to the aspect ratio of `size` first.
+    pub(super) fn resize(
+        image: &DynamicImage,
+        size: (u32, u32),
+        fill: bool,
+    ) -> ThumbsResult<DynamicImage> {
+        let mut options =
+            ResizeOptions::new().resize_alg(ResizeAlg::Convolution(FilterType::Lanczos3));
+        if fill {
+            options = options.fit_into_destination(Some((0.5, 0.5)));
+        }
+
+        let mut resized = DynamicImage::new(size.0, size.1, image.color());
+        Resizer::new()
+            .resize(image, &mut resized, &options)
+            .map_err(|error| Error::Resize(error.to_string()))?;
+        Ok(resized)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{ColorType, GenericImageView};
+
+    use super::*;
+
+    #[test]
+    fn fit_dimensions_like_thumbnail() {
+        for (size, bounds) in [
+            ((100, 50), (30, 30)),
+            ((33, 100), (20, 7)),
+            ((1000, 1), (10, 10)),
+            ((640, 480), (1000, 1000)),
+        ] {
+            let thumbnail =
+                DynamicImage::new(size.0, size.1, ColorType::L8).thumbnail(bounds.0, bounds.1);
+            assert_eq!(fit_dimensions(size, bounds), thumbnail.dimensions());
+        }
+    }
+
+    #[test]
+    fn resize_to_size() {
+        let image = DynamicImage::new(100, 50, ColorType::Rgb8);
+        assert_eq!(resize_fit(&image, (30, 30)).unwrap().dimensions(), (30, 15));
+        assert_eq!(
+            resize_fill(&image, (30, 30)).unwrap().dimensions(),
+            (30, 30)
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind};
+use std::time::Duration;
+
+use object_store::ObjectStore;
+
+use crate::checksum::CHECKSUM_STORE;
+use crate::ImageThumbs;
+
+/// Parts of the messages of timeouts and of `5xx` and `429` responses of the HTTP stores
+const TRANSIENT_MESSAGES: [&str; 4] = [
+    "timed out",
+    "with status 5",
+    "status server error",
+    "429 Too Many Requests",
+];
+
+/// How requests to the store are retried after transient errors, e.g., a `503` of the provider.
+/// Permanent errors, like a missing object or denied access, are never retried.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Retries {
+    /// Number of attempts of every request, including the first one. `0` and `1` disable
+    /// retrying.
+    pub attempts: u32,
+    /// Delay before the first retry; doubled for every following retry
+    pub backoff: Duration,
+    /// Fraction between 0 and 1 by which every delay is randomly shortened, so that concurrent
+    /// requests failing at the same time do not retry in lockstep
+    pub jitter: f32,
+}
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Retries downloading images, checking for existent thumbnails, and uploading thumbnails
+    /// after transient storage errors, so that one overloaded moment of the provider does not
+    /// abort a whole batch.
+    pub fn with_retries(mut self, retries: Retries) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Runs the request, retrying it according to [`ImageThumbs::with_retries`] and waiting for
+    /// the [`crate::RateLimit`] before every attempt.
+    pub(crate) async fn retrying<R, F>(
+        &self,
+        mut request: impl FnMut() -> F,
+    ) -> object_store::Result<R>
+    where
+        F: Future<Output = object_store::Result<R>>,
+    {
+        retry(self.retries, || {
+            let request = request();
+            async move {
+                let _permit = self.limiter.acquire().await;
+                request.await
+            }
+        })
+        .await
+    }
+}
+
+/// Runs the request until it succeeds, fails permanently, or runs out of attempts.
+pub(crate) async fn retry<R, F>(
+    retries: Retries,
+    mut request: impl FnMut() -> F,
+) -> object_store::Result<R>
+where
+    F: Future<Output = object_store::Result<R>>,
+{
+    let mut backoff = retries.backoff;
+    let mut attempt = 1;
+    loop {
+        match request().await {
+            Err(e) if is_transient(&e) && attempt < retries.attempts => {
+                let jitter = retries.jitter.clamp(0.0, 1.0) * random_fraction();
+                tokio::time::sleep(backoff.mul_f32(1.0 - jitter)).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether the request may succeed when repeated, i.e., it timed out, the connection was
+/// interrupted, the provider was unavailable (`5xx` or `429`), or the download was incomplete.
+/// The errors of the HTTP stores are not public, so their status is recognized by the message.
+fn is_transient(error: &object_store::Error) -> bool {
+    let object_store::Error::Generic { store, source } = error else {
+        return false;
+    };
+    if *store == CHECKSUM_STORE {
+        return true;
+    }
+    let mut cause: Option<&(dyn std::error::Error + 'static)> = Some(source.as_ref());
+    while let Some(error) = cause {
+        if let Some(error) = error.downcast_ref::<io::Error>() {
+            if matches!(
+                error.kind(),
+                ErrorKind::TimedOut
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::UnexpectedEof
+                    | ErrorKind::Interrupted
+            ) {
+                return true;
+            }
+        }
+        let message = error.to_string();
+        if TRANSIENT_MESSAGES
+            .iter()
+            .any(|transient| message.contains(transient))
+        {
+            return true;
+        }
+        cause = error.source();
+    }
+    false
+}
+
+/// Random number between 0 and 1, without seeding a generator for every delay
+fn random_fraction() -> f32 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 40) as f32 / (1u64 << 24) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    use std::io::{self, ErrorKind};
+
+    use super::{is_transient, retry, Retries};
+    use crate::checksum::CHECKSUM_STORE;
+
+    #[tokio::test]
+    async fn retry_transient_errors() {
+        let retries = Retries {
+            attempts: 3,
+            backoff: Duration::from_millis(1),
+            jitter: 0.5,
+        };
+        let transient = || object_store::Error::Generic {
+            store: "test",
+            source: "with status 503 Service Unavailable".into(),
+        };
+
+        let calls = AtomicU32::new(0);
+        let result = retry(retries, || async {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(transient()),
+                _ => Ok(42),
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let calls = AtomicU32::new(0);
+        let result: object_store::Result<()> = retry(retries, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(transient())
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let calls = AtomicU32::new(0);
+        let result: object_store::Result<()> = retry(retries, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(object_store::Error::NotFound {
+                path: "penguin.png".to_string(),
+                source: "missing".into(),
+            })
+        })
+        .await;
+        assert!(matches!(result, Err(object_store::Error::NotFound { .. })));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn transient_errors() {
+        let generic =
+            |source: Box<dyn std::error::Error + Send + Sync>| object_store::Error::Generic {
+                store: "S3",
+                source,
+            };
+        for source in [
+            "Server error, body contains Error, with status 503 Service Unavailable: No Body"
+                .into(),
+            "Error after 10 retries, source:operation timed out".into(),
+            Box::new(io::Error::from(ErrorKind::ConnectionReset)) as _,
+        ] {
+            assert!(is_transient(&generic(source)));
+        }
+        for source in [
+            "Client error with status 400 Bad Request: No Body".into(),
+            "invalid configuration".into(),
+            Box::new(io::Error::from(ErrorKind::InvalidData)) as _,
+        ] {
+            assert!(!is_transient(&generic(source)));
+        }
+        assert!(is_transient(&object_store::Error::Generic {
+            store: CHECKSUM_STORE,
+            source: "incomplete".into(),
+        }));
+        assert!(!is_transient(&object_store::Error::NotImplemented));
+    }
+}
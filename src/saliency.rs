@@ -0,0 +1,192 @@
+use image::{DynamicImage, GrayImage};
+
+/// Size of the longer edge of the downscaled image the saliency is computed on
+const ANALYSIS_SIZE: u32 = 64;
+/// Number of brightness buckets used to compute the entropy of a region
+const ENTROPY_BINS: usize = 32;
+
+/// Finds the crop window with the target aspect ratio that contains the most edge and contrast
+/// energy and returns its center in percent (i.e., between 0 and 1), as expected by
+/// `crop_aspect_ratio_with_center`.
+///
+/// Images without any salient region (e.g., a single color) are centered at `(0.5, 0.5)`.
+pub(crate) fn salient_center(image: &DynamicImage, target_size: (u32, u32)) -> (f32, f32) {
+    let small = analysis_image(image);
+    let (width, height) = small.dimensions();
+    if width < 3 || height < 3 || target_size.0 == 0 || target_size.1 == 0 {
+        return (0.5, 0.5);
+    }
+
+    // summed-area table of the gradient magnitude, with an additional zero row and column
+    let stride = width as usize + 1;
+    let mut integral = vec![0_u64; stride * (height as usize + 1)];
+    for y in 0..height {
+        let mut row_sum = 0_u64;
+        for x in 0..width {
+            let energy = if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                0
+            } else {
+                let dx = small.get_pixel(x + 1, y)[0].abs_diff(small.get_pixel(x - 1, y)[0]);
+                let dy = small.get_pixel(x, y + 1)[0].abs_diff(small.get_pixel(x, y - 1)[0]);
+                dx as u64 + dy as u64
+            };
+            row_sum += energy;
+            let i = (y as usize + 1) * stride + x as usize + 1;
+            integral[i] = integral[i - stride] + row_sum;
+        }
+    }
+
+    let (crop_width, crop_height) = crop_window((width, height), target_size);
+    best_window_center((width, height), (crop_width, crop_height), |x, y| {
+        let (x0, y0) = (x as usize, y as usize);
+        let (x1, y1) = (x0 + crop_width as usize, y0 + crop_height as usize);
+        (integral[y1 * stride + x1] + integral[y0 * stride + x0]
+            - integral[y0 * stride + x1]
+            - integral[y1 * stride + x0]) as f64
+    })
+}
+
+/// Finds the crop window with the target aspect ratio whose brightness histogram has the highest
+/// entropy, i.e., which contains the most detail, and returns its center in percent (i.e., between
+/// 0 and 1), as expected by `crop_aspect_ratio_with_center`.
+///
+/// Images without any detail (e.g., a single color) are centered at `(0.5, 0.5)`.
+pub(crate) fn entropy_center(image: &DynamicImage, target_size: (u32, u32)) -> (f32, f32) {
+    let small = analysis_image(image);
+    let (width, height) = small.dimensions();
+    if width == 0 || height == 0 || target_size.0 == 0 || target_size.1 == 0 {
+        return (0.5, 0.5);
+    }
+
+    let (crop_width, crop_height) = crop_window((width, height), target_size);
+    best_window_center((width, height), (crop_width, crop_height), |x, y| {
+        let mut histogram = [0_u32; ENTROPY_BINS];
+        for y in y..y + crop_height {
+            for x in x..x + crop_width {
+                histogram[small.get_pixel(x, y)[0] as usize * ENTROPY_BINS / 256] += 1;
+            }
+        }
+        let total = (crop_width * crop_height) as f64;
+        histogram
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / total;
+                -p * p.log2()
+            })
+            .sum()
+    })
+}
+
+fn analysis_image(image: &DynamicImage) -> GrayImage {
+    image.thumbnail(ANALYSIS_SIZE, ANALYSIS_SIZE).to_luma8()
+}
+
+/// Largest window with the target aspect ratio that fits into an image of the given size
+fn crop_window(size: (u32, u32), target_size: (u32, u32)) -> (u32, u32) {
+    let (width, height) = size;
+    let target_aspect_ratio = target_size.0 as f32 / target_size.1 as f32;
+    let (crop_width, crop_height) = if width as f32 / height as f32 > target_aspect_ratio {
+        ((target_aspect_ratio * height as f32).round() as u32, height)
+    } else {
+        (width, (width as f32 / target_aspect_ratio).round() as u32)
+    };
+    (crop_width.clamp(1, width), crop_height.clamp(1, height))
+}
+
+/// Returns the center of the window position with the highest score in percent, preferring
+/// windows closer to the middle of the image if several score equally.
+fn best_window_center(
+    size: (u32, u32),
+    crop: (u32, u32),
+    score: impl Fn(u32, u32) -> f64,
+) -> (f32, f32) {
+    let (width, height) = size;
+    let (crop_width, crop_height) = crop;
+
+    let middle = (
+        (width - crop_width) as f32 / 2.,
+        (height - crop_height) as f32 / 2.,
+    );
+    let distance = |x: u32, y: u32| (x as f32 - middle.0).abs() + (y as f32 - middle.1).abs();
+
+    let mut best = (0, 0);
+    let mut best_score = f64::NEG_INFINITY;
+    for y in 0..=height - crop_height {
+        for x in 0..=width - crop_width {
+            let score = score(x, y);
+            if score > best_score
+                || (score == best_score && distance(x, y) < distance(best.0, best.1))
+            {
+                best = (x, y);
+                best_score = score;
+            }
+        }
+    }
+
+    if best_score <= 0. {
+        return (0.5, 0.5);
+    }
+    (
+        (best.0 as f32 + crop_width as f32 / 2.) / width as f32,
+        (best.1 as f32 + crop_height as f32 / 2.) / height as f32,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use image::{ColorType, DynamicImage, GrayImage, Luma};
+
+    use super::*;
+
+    fn checkerboard(size: (u32, u32), area: impl Fn(u32, u32) -> bool) -> DynamicImage {
+        let mut image = GrayImage::new(size.0, size.1);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            if area(x, y) && (x / 5 + y / 5) % 2 == 0 {
+                *pixel = Luma([255]);
+            }
+        }
+        DynamicImage::from(image)
+    }
+
+    #[test]
+    fn uniform_image_is_centered() {
+        let image = DynamicImage::new(300, 100, ColorType::L8);
+        assert_eq!(salient_center(&image, (10, 10)), (0.5, 0.5));
+        assert_eq!(entropy_center(&image, (10, 10)), (0.5, 0.5));
+    }
+
+    #[test]
+    fn follows_salient_region() {
+        let left = checkerboard((300, 100), |x, _| x < 80);
+        let center = salient_center(&left, (10, 10));
+        assert!(
+            center.0 < 0.3,
+            "center should be on the left, was {center:?}"
+        );
+
+        let bottom = checkerboard((100, 300), |_, y| y > 220);
+        let center = salient_center(&bottom, (10, 10));
+        assert!(
+            center.1 > 0.7,
+            "center should be at the bottom, was {center:?}"
+        );
+    }
+
+    #[test]
+    fn follows_high_entropy_region() {
+        let left = checkerboard((300, 100), |x, _| x < 80);
+        let center = entropy_center(&left, (10, 10));
+        assert!(
+            center.0 < 0.3,
+            "center should be on the left, was {center:?}"
+        );
+
+        let bottom = checkerboard((100, 300), |_, y| y > 220);
+        let center = entropy_center(&bottom, (10, 10));
+        assert!(
+            center.1 > 0.7,
+            "center should be at the bottom, was {center:?}"
+        );
+    }
+}
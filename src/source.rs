@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+#[cfg(feature = "http_source")]
+use object_store::http::HttpBuilder;
+use object_store::ObjectStore;
+
+use crate::ImageThumbs;
+#[cfg(feature = "http_source")]
+use crate::ThumbsResult;
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Reads the source images from a separate store, e.g., a read-only CDN, instead of the store
+    /// the thumbnails are written to.
+    ///
+    /// Image paths passed to this instance are then relative to the source store, while
+    /// `dest_dir` stays relative to the primary store.
+    ///
+    /// The source may be a different provider with its own credentials, e.g., to read from GCS
+    /// and write to S3 while migrating between clouds. Build it with the re-exported
+    /// [`object_store`] crate.
+    pub fn with_source(mut self, source: Arc<dyn ObjectStore>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Reads the source images via plain HTTP(S) requests below the given URL, e.g.,
+    /// `https://cdn.example.com/originals`.
+    ///
+    /// HTTP sources cannot be listed, so only single images can be processed, not whole
+    /// directories.
+    #[cfg(feature = "http_source")]
+    pub fn with_http_source(self, url: &str) -> ThumbsResult<Self> {
+        let source = HttpBuilder::new()
+            .with_url(url)
+            .with_client_options(Self::client_options())
+            .build()?;
+        Ok(self.with_source(Arc::new(source)))
+    }
+
+    /// Store the source images are read from
+    pub(crate) fn source(&self) -> &dyn ObjectStore {
+        self.source_or(&self.client)
+    }
+
+    /// Store the source images are read from, if the thumbnails are written to `store`
+    pub(crate) fn source_or<'a>(&'a self, store: &'a dyn ObjectStore) -> &'a dyn ObjectStore {
+        self.source.as_deref().unwrap_or(store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use object_store::local::LocalFileSystem;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    use crate::model::Params;
+    use crate::test_utils::png;
+    use crate::ImageThumbs;
+
+    #[tokio::test]
+    async fn read_from_source() {
+        let source = Arc::new(InMemory::new());
+        let bytes = png();
+        source
+            .put(&Path::parse("originals/penguin.png").unwrap(), bytes.into())
+            .await
+            .unwrap();
+
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings).with_source(source.clone());
+        client
+            .create_thumbs("originals/penguin.png", "/thumbs", false)
+            .await
+            .unwrap();
+
+        let thumb = Path::parse("thumbs/penguin_mini.png").unwrap();
+        client.client.head(&thumb).await.unwrap();
+        assert!(source.head(&thumb).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn per_call_store() {
+        let other = InMemory::new();
+        let bytes = png();
+        other
+            .put(&Path::parse("penguin.png").unwrap(), bytes.into())
+            .await
+            .unwrap();
+
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        client
+            .create_thumbs_dir_in(&other, None, "/thumbs", false)
+            .await
+            .unwrap();
+
+        let thumb = Path::parse("thumbs/penguin_mini.png").unwrap();
+        other.head(&thumb).await.unwrap();
+        assert!(client.client.head(&thumb).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn read_from_other_provider() {
+        let root = std::env::temp_dir().join(format!("image_thumbs_source_{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let bytes = png();
+        std::fs::write(root.join("penguin.png"), bytes).unwrap();
+
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let source = LocalFileSystem::new_with_prefix(&root).unwrap();
+        let client =
+            ImageThumbs::from_parts(InMemory::new(), settings).with_source(Arc::new(source));
+        client
+            .create_thumbs("penguin.png", "/thumbs", false)
+            .await
+            .unwrap();
+
+        let thumb = Path::parse("thumbs/penguin_mini.png").unwrap();
+        client.client.head(&thumb).await.unwrap();
+        assert!(!root.join("thumbs").exists());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
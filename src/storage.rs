@@ -1,10 +1,30 @@
+use std::collections::HashSet;
+
+use futures::TryStreamExt;
 use image::{guess_format, ImageFormat};
 use object_store::path::{Path, PathPart};
-use object_store::{ClientOptions, ObjectMeta, ObjectStore};
+use object_store::{
+    ClientOptions, GetOptions, GetRange, ObjectStore, PutMode, PutOptions, PutPayload,
+};
+
+use crate::checksum::verify_checksum;
+use crate::model::{CreatedThumb, ImageDetails, Params, ThumbReport};
+use crate::timeout::with_timeout;
+use crate::Error::{ContentDependentPath, NotSupported};
+use crate::{Error, ImageThumbs, ThumbsResult};
+
+/// Naming pattern of thumbnails whose preset does not define one
+const DEFAULT_NAMING_PATTERN: &str = "/{image_stem}_{thumb_name}";
 
-use crate::model::ImageDetails;
-use crate::Error::NotSupported;
-use crate::{ImageThumbs, ThumbsResult};
+/// Placeholder of naming patterns for the hash of the contents of the source image, which is only
+/// known once the image is downloaded
+pub(crate) const SOURCE_HASH_PLACEHOLDER: &str = "{source_hash}";
+
+/// Prefix of destination directories that are relative to the folder of each image
+const RELATIVE_DEST_DIR: &str = "./";
+
+/// Number of leading bytes fetched to recognize the format of an object without downloading it
+const SNIFF_LENGTH: usize = 512;
 
 impl<T: ObjectStore> ImageThumbs<T> {
     /// Returns options for an [`object_store`] client that maps the file extensions `.jpeg`,
@@ -25,15 +45,50 @@ impl<T: ObjectStore> ImageThumbs<T> {
         client_options
     }
 
-    pub(crate) async fn upload_thumbs(&self, images: Vec<ImageDetails>) -> ThumbsResult<()> {
+    /// Stores the thumbnails and copies them to the replicas. Unless `force_override` is set,
+    /// thumbnails that already exist are kept and left out of the result.
+    pub(crate) async fn upload_thumbs(
+        &self,
+        store: &dyn ObjectStore,
+        images: Vec<ImageDetails>,
+        force_override: bool,
+    ) -> ThumbsResult<Vec<CreatedThumb>> {
+        let mut res = Vec::with_capacity(images.len());
         for image in images {
-            let path = Self::generate_path(&image.path, &image.stem, &image.format);
-            self.client
-                .put(&Path::parse(path)?, image.bytes.into())
-                .await?;
+            let path = Path::parse(Self::generate_path(&image.path, &image.stem, &image.format))?;
+            let created = image.dimensions.map(|dimensions| CreatedThumb {
+                path: path.clone(),
+                format: image.format,
+                dimensions,
+                size: image.bytes.len(),
+            });
+            let payload = PutPayload::from(image.bytes);
+            let options = PutOptions {
+                attributes: image.attributes,
+                ..Default::default()
+            };
+            let mode = if force_override {
+                PutMode::Overwrite
+            } else {
+                // also keeps a thumbnail that another worker created in the meantime
+                PutMode::Create
+            };
+            let primary = PutOptions {
+                mode,
+                ..options.clone()
+            };
+            if !self
+                .put_object(store, &path, payload.clone(), primary)
+                .await
+                .map_err(|e| e.context("uploading", &path))?
+            {
+                continue; // already exists
+            }
+            self.replicate(&path, payload, options);
+            res.extend(created);
         }
 
-        Ok(())
+        Ok(res)
     }
 
     pub(crate) fn generate_path(
@@ -50,21 +105,119 @@ impl<T: ObjectStore> ImageThumbs<T> {
         )
     }
 
+    /// Fills in the placeholders of the naming pattern for the given image and preset.
+    ///
+    /// # Arguments
+    /// * `format` - format the thumbnail is stored in, used for `{format}`.
     pub(crate) fn generate_thumb_stem(
         image_stem: &str,
-        thumb_name: &str,
+        params: &Params,
         naming_pattern: &str,
+        format: ImageFormat,
     ) -> String {
-        naming_pattern
-            .replace("{thumb_name}", thumb_name)
+        Self::expand_naming_pattern(naming_pattern, params, format)
             .replace("{image_stem}", image_stem)
     }
 
+    /// Replaces all placeholders except `{image_stem}`, which are the same for every image.
+    fn expand_naming_pattern(naming_pattern: &str, params: &Params, format: ImageFormat) -> String {
+        naming_pattern
+            .replace("{thumb_name}", &params.name)
+            .replace("{width}", &params.size.0.to_string())
+            .replace("{height}", &params.size.1.to_string())
+            .replace("{format}", format.extensions_str()[0])
+            .replace("{quality}", &params.quality.to_string())
+            .replace("{hash}", &params.fingerprint())
+    }
+
+    /// Stem of the thumbnail of the given preset, i.e., its path below the destination directory
+    /// without extension
+    pub(crate) fn thumb_stem(image_stem: &str, params: &Params, format: ImageFormat) -> String {
+        let naming_pattern = params
+            .naming_pattern
+            .as_deref()
+            .unwrap_or(DEFAULT_NAMING_PATTERN);
+        Self::generate_thumb_stem(image_stem, params, naming_pattern, format)
+    }
+
+    /// Reverses [`ImageThumbs::thumb_stem`], i.e., returns the stem of the image the thumbnail
+    /// with the given stem and format would have been created from with this preset.
+    pub(crate) fn image_stem_of<'a>(
+        thumb_stem: &'a str,
+        params: &Params,
+        format: ImageFormat,
+    ) -> Option<&'a str> {
+        let naming_pattern = params
+            .naming_pattern
+            .as_deref()
+            .unwrap_or(DEFAULT_NAMING_PATTERN);
+        let naming_pattern = Self::expand_naming_pattern(naming_pattern, params, format);
+        let naming_pattern = naming_pattern.strip_prefix('/').unwrap_or(&naming_pattern);
+        let (prefix, suffix) = naming_pattern.split_once("{image_stem}")?;
+        thumb_stem
+            .strip_prefix(prefix)?
+            .strip_suffix(suffix)
+            .filter(|stem| !stem.is_empty())
+    }
+
+    /// Paths of the thumbnails of all presets for the given source image, derived from its name
+    /// and extension. The presets of a profile mapped to the image's path are used instead of the
+    /// configured ones.
+    ///
+    /// Fails with [`Error::ContentDependentPath`](crate::Error::ContentDependentPath) for presets
+    /// of which the naming pattern contains the hash of the image.
+    pub(crate) fn thumb_paths(
+        &self,
+        file: &Path,
+        dest_dir: &Path,
+        nested: &Path,
+    ) -> ThumbsResult<Vec<(Params, Path)>> {
+        let stem = Self::extract_stem(file)?;
+        let format = ImageFormat::from_extension(file.extension().ok_or(NotSupported)?)
+            .ok_or(NotSupported)?;
+        self.presets_for(file)?
+            .iter()
+            .map(|params| {
+                let format = params.output_format(format);
+                let thumb_stem = Self::thumb_stem(stem, params, format);
+                if thumb_stem.contains(SOURCE_HASH_PLACEHOLDER) {
+                    return Err(ContentDependentPath(params.name.clone()));
+                }
+                let thumb_dir = params.thumb_dir(dest_dir, nested)?;
+                let path = Self::generate_path(&thumb_dir, &thumb_stem, &format);
+                Ok((params.clone(), Path::parse(path)?))
+            })
+            .collect()
+    }
+
     pub(crate) async fn download_image(&self, path: &str) -> ThumbsResult<ImageDetails> {
-        let result = self.client.get(&Path::parse(path)?).await?;
-        let stem = Self::extract_stem(&result.meta.location)?.to_string();
+        with_timeout(
+            self.timeouts.download,
+            "downloading",
+            self.download_image_from(self.source(), path),
+        )
+        .await
+    }
 
-        let path = result.meta.location.parts().collect::<Vec<PathPart>>();
+    pub(crate) async fn download_image_from(
+        &self,
+        store: &dyn ObjectStore,
+        path: &str,
+    ) -> ThumbsResult<ImageDetails> {
+        let path = Path::parse(path)?;
+        let (meta, attributes, bytes) = self
+            .retrying(|| async {
+                let result = store.get(&path).await?;
+                let (meta, attributes) = (result.meta.clone(), result.attributes.clone());
+                let bytes = result.bytes().await?;
+                verify_checksum(&meta, &attributes, &bytes)?;
+                Ok((meta, attributes, bytes))
+            })
+            .await
+            .map_err(|e| source_error(e, &path))?;
+        let stem = Self::extract_stem(&meta.location)?.to_string();
+
+        let path = meta.location.parts().collect::<Vec<PathPart>>();
         let path = if path.len() > 1 {
             path[..path.len() - 2]
                 .iter()
@@ -73,18 +226,64 @@ impl<T: ObjectStore> ImageThumbs<T> {
             Path::from("/")
         };
 
-        let bytes = result.bytes().await?.to_vec();
-        let format = guess_format(&bytes)?;
+        let format = guess_format(&bytes)
+            .map_err(|e| Error::from(e).context("detecting the format of", &meta.location))?;
 
         Ok(ImageDetails {
             stem,
             format,
             path,
             bytes,
+            attributes,
+            dimensions: None,
+            e_tag: meta.e_tag,
         })
     }
 
-    fn extract_stem(path: &Path) -> ThumbsResult<&str> {
+    /// Recognizes the image format of the object from its first bytes, using a ranged request
+    /// instead of downloading the whole object. Fails with [`NotSupported`] for objects that are
+    /// no supported image, e.g., videos.
+    pub(crate) async fn sniff_format(
+        &self,
+        store: &dyn ObjectStore,
+        path: &Path,
+    ) -> ThumbsResult<ImageFormat> {
+        let header = self
+            .retrying(|| async {
+                let options = GetOptions {
+                    range: Some(GetRange::Bounded(0..SNIFF_LENGTH)),
+                    ..Default::default()
+                };
+                store.get_opts(path, options).await?.bytes().await
+            })
+            .await
+            .map_err(|e| source_error(e, path))?;
+        guess_format(&header).map_err(|e| Error::from(e).context("detecting the format of", path))
+    }
+
+    /// Directory the thumbnails of the given image are stored in. A `dest_dir` starting with `./`,
+    /// e.g., `./thumbs`, is relative to the folder of the image; others are used as they are.
+    pub(crate) fn resolve_dest_dir(dest_dir: &str, image: &str) -> ThumbsResult<Path> {
+        if !Self::is_relative_dest_dir(dest_dir) {
+            return Ok(Path::parse(dest_dir)?);
+        }
+        let relative = dest_dir.strip_prefix(RELATIVE_DEST_DIR).unwrap_or_default();
+        let image = Path::parse(image)?;
+        let mut folder: Vec<PathPart> = image.parts().collect();
+        folder.pop();
+        Ok(folder
+            .into_iter()
+            .chain(Path::parse(relative)?.parts())
+            .collect())
+    }
+
+    /// Whether the destination directory is relative to the folder of each image, e.g., `./thumbs`
+    /// or `.`.
+    pub(crate) fn is_relative_dest_dir(dest_dir: &str) -> bool {
+        dest_dir == "." || dest_dir.starts_with(RELATIVE_DEST_DIR)
+    }
+
+    pub(crate) fn extract_stem(path: &Path) -> ThumbsResult<&str> {
         let (stem, _) = match path.filename() {
             None => Err(NotSupported)?,
             Some(filename) => filename.rsplit_once('.').unwrap_or((filename, "")),
@@ -92,9 +291,11 @@ impl<T: ObjectStore> ImageThumbs<T> {
         Ok(stem)
     }
 
-    pub(crate) async fn list_folder(&self, prefix: Option<&Path>) -> ThumbsResult<Vec<Path>> {
-        Ok(self
-            .client
+    pub(crate) async fn list_folder(
+        store: &dyn ObjectStore,
+        prefix: Option<&Path>,
+    ) -> ThumbsResult<Vec<Path>> {
+        Ok(store
             .list_with_delimiter(prefix)
             .await?
             .objects
@@ -103,44 +304,48 @@ impl<T: ObjectStore> ImageThumbs<T> {
             .collect::<Vec<Path>>())
     }
 
-    pub(crate) async fn head(&self, path: &Path) -> ThumbsResult<ObjectMeta> {
-        Ok(self.client.head(path).await?)
+    /// Lists all objects below the prefix, including those in nested directories.
+    pub(crate) async fn list_folder_recursive(
+        store: &dyn ObjectStore,
+        prefix: Option<&Path>,
+    ) -> ThumbsResult<Vec<Path>> {
+        Ok(store
+            .list(prefix)
+            .map_ok(|meta| meta.location)
+            .try_collect()
+            .await?)
     }
 
+    /// Returns the images of the reports that are missing at least one of their thumbnails.
+    /// Objects without a supported image extension are always returned.
     pub(crate) fn filter_existent_thumbs(
         &self,
-        images: Vec<Path>,
+        reports: &[ThumbReport],
         thumbs: &[Path],
-    ) -> ThumbsResult<Vec<Path>> {
-        let mut res = Vec::new();
-        for image in images {
-            let format = ImageFormat::from_extension(image.extension().ok_or(NotSupported)?)
-                .ok_or(NotSupported)?;
-            let file_stem = image.filename().ok_or(NotSupported)?;
-            let mut has_all_thumbs = true;
-            for params in self.settings.clone() {
-                let target_name = format!(
-                    "{}_{}.{}",
-                    file_stem,
-                    params.name,
-                    format.extensions_str()[0]
-                );
-                let thumb_names: Vec<_> = thumbs.iter().map(|p| p.filename()).collect();
-                if !thumb_names.contains(&Some(&target_name)) {
-                    has_all_thumbs = false;
-                    break;
-                }
-            }
+    ) -> ThumbsResult<HashSet<Path>> {
+        let thumbs: HashSet<&Path> = thumbs.iter().collect();
+        let mut res = HashSet::new();
+        for report in reports {
+            let paths = self.thumb_paths(&report.image, &report.dest_dir, &report.nested);
+            let has_all_thumbs = match paths {
+                Ok(paths) => paths.iter().all(|(_, path)| thumbs.contains(path)),
+                // left to fail or to be skipped on its own instead of aborting the whole listing
+                Err(NotSupported | ContentDependentPath(_)) => false,
+                Err(e) => return Err(e),
+            };
             if !has_all_thumbs {
-                res.push(image);
+                res.insert(report.image.clone());
             }
         }
         Ok(res)
     }
+}
 
-    #[cfg(test)]
-    pub(crate) async fn delete(&self, path: &str) -> ThumbsResult<()> {
-        self.client.delete(&Path::parse(path)?).await?;
-        Ok(())
+/// Error of a failed download of the source image, telling a missing image apart from other
+/// storage errors.
+fn source_error(error: object_store::Error, path: &Path) -> Error {
+    match error {
+        object_store::Error::NotFound { .. } => Error::SourceNotFound(path.to_string()),
+        error => Error::from(error).context("downloading", path),
     }
 }
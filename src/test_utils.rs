@@ -0,0 +1,36 @@
+//! Fixtures shared by the tests of all modules.
+
+use image::{DynamicImage, ImageFormat};
+use object_store::memory::InMemory;
+use object_store::path::Path;
+use object_store::ObjectStore;
+
+use crate::image::encode;
+use crate::model::Params;
+use crate::ImageThumbs;
+
+/// Encoded 40x20 PNG that is used as source image
+pub(crate) fn png() -> Vec<u8> {
+    encode(&DynamicImage::new_rgb8(40, 20), ImageFormat::Png, 100, None).unwrap()
+}
+
+/// Stores the bytes at all given paths of the store.
+pub(crate) async fn put_all(store: &dyn ObjectStore, paths: &[&str], bytes: &[u8]) {
+    for path in paths {
+        store
+            .put(&Path::parse(path).unwrap(), bytes.to_vec().into())
+            .await
+            .unwrap();
+    }
+}
+
+/// Instance on an in-memory store with the given presets, in which the [`png`] is stored at all
+/// given paths.
+pub(crate) async fn client_with_images(
+    settings: Vec<Params>,
+    images: &[&str],
+) -> ImageThumbs<InMemory> {
+    let client = ImageThumbs::from_parts(InMemory::new(), settings);
+    put_all(&client.client, images, &png()).await;
+    client
+}
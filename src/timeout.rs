@@ -0,0 +1,100 @@
+use std::future::Future;
+use std::time::Duration;
+
+use object_store::ObjectStore;
+
+use crate::{Error, ImageThumbs, ThumbsResult};
+
+/// Maximum durations of the phases of creating thumbnails for one image. Phases without a limit
+/// may take arbitrarily long.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Downloading the source image
+    pub download: Option<Duration>,
+    /// Decoding the image and computing and encoding all thumbnails. The call fails as soon as
+    /// the limit is exceeded; the computation itself cannot be interrupted and stops after the
+    /// current preset.
+    pub encode: Option<Duration>,
+    /// Uploading all thumbnails
+    pub upload: Option<Duration>,
+}
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Limits the duration of downloading, encoding, and uploading for every image, so that one
+    /// hung request cannot stall a whole batch. Exceeding a limit fails with [`Error::Timeout`].
+    ///
+    /// Directory runs can override the limits with [`crate::DirOptions::timeouts`].
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+}
+
+/// Awaits the future, failing with [`Error::Timeout`] for the given phase if it takes longer than
+/// the limit.
+pub(crate) async fn with_timeout<R>(
+    limit: Option<Duration>,
+    phase: &'static str,
+    future: impl Future<Output = ThumbsResult<R>>,
+) -> ThumbsResult<R> {
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, future)
+            .await
+            .map_err(|_| Error::Timeout(phase))?,
+        None => future.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::pending;
+    use std::time::Duration;
+
+    use image::ImageFormat;
+    use object_store::memory::InMemory;
+
+    use super::{with_timeout, Timeouts};
+    use crate::model::Params;
+    use crate::test_utils::png;
+    use crate::{Error, ImageThumbs};
+
+    #[tokio::test]
+    async fn hung_future_times_out() {
+        let result = with_timeout(
+            Some(Duration::from_millis(10)),
+            "downloading",
+            pending::<Result<(), Error>>(),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::Timeout("downloading"))));
+
+        let result = with_timeout(None, "downloading", async { Ok(1) }).await;
+        assert_eq!(result.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn encode_timeout() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings).with_timeouts(Timeouts {
+            encode: Some(Duration::ZERO),
+            ..Default::default()
+        });
+        let bytes = png();
+
+        let result = client
+            .create_thumbs_from_bytes(
+                bytes,
+                "thumbs",
+                "penguin",
+                ImageFormat::Png,
+                false,
+                (0.5, 0.5),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Timeout("encoding"))));
+    }
+}
@@ -0,0 +1,255 @@
+use bytes::Bytes;
+use image::{guess_format, ImageFormat};
+use object_store::path::Path;
+use object_store::{ObjectStore, PutOptions, PutPayload};
+
+use crate::model::CreateOptions;
+use crate::{Error, ImageHashes, ImageThumbs, ThumbsResult};
+
+impl<T: ObjectStore> ImageThumbs<T> {
+    /// Reads an image from the store, or from the source store if one is configured with
+    /// [`ImageThumbs::with_source`], e.g., to serve the original.
+    ///
+    /// Like all downloads of images, it is retried if fewer bytes than the size of the object
+    /// arrive, or if the contents do not match the MD5 stored with them by
+    /// [`ImageThumbs::upload`].
+    ///
+    /// # Arguments
+    /// * `file` - path of the image.
+    ///
+    /// Returns the format, detected from the contents, and the raw bytes of the image.
+    pub async fn download(&self, file: &str) -> ThumbsResult<(ImageFormat, Bytes)> {
+        let image = self.download_image(file).await?;
+        Ok((image.format, image.bytes))
+    }
+
+    /// Stores the given bytes in the store, and in the background on all replicas added with
+    /// [`ImageThumbs::with_replica`], e.g., an image that was generated elsewhere. The MD5 of the
+    /// bytes is stored as `content_md5` metadata, so that downloads can be verified.
+    ///
+    /// # Arguments
+    /// * `path` - path to store the bytes at. Existent files are overridden.
+    ///
+    /// * `bytes` - raw bytes to store.
+    pub async fn upload(&self, path: &str, bytes: Bytes) -> ThumbsResult<()> {
+        let path = Path::parse(path)?;
+        let payload = PutPayload::from(bytes);
+        self.put_object(&self.client, &path, payload.clone(), PutOptions::default())
+            .await?;
+        self.replicate(&path, payload, PutOptions::default());
+        Ok(())
+    }
+
+    /// Stores an uploaded image at the given path and creates all thumbnails for it in one call.
+    ///
+    /// The thumbnails are created before anything is stored, so images that cannot be decoded
+    /// are rejected without leaving an original behind. Existent thumbnails of a previous image
+    /// at the same path are overridden.
+    ///
+    /// # Arguments
+    /// * `bytes` - raw bytes of the uploaded image. The format is detected from the contents.
+    ///
+    /// * `path` - path to store the original at, e.g., `originals/penguin.jpg`.
+    ///
+    /// * `dest_dir` - directory to store all created thumbnails.
+    ///
+    /// * `atomic` - if `true`, the original and all stored thumbnails are deleted again when
+    ///   storing one of them fails. If `false`, whatever was stored is kept. Fails with the error
+    ///   of the deletion if it fails itself.
+    ///
+    /// Returns the placeholder hashes of the image, if enabled.
+    pub async fn ingest(
+        &self,
+        bytes: impl Into<Bytes>,
+        path: &str,
+        dest_dir: &str,
+        atomic: bool,
+    ) -> ThumbsResult<ImageHashes> {
+        let bytes = bytes.into();
+        let format = guess_format(&bytes)?;
+        let stem = Self::extract_stem(&Path::parse(path)?)?.to_string();
+        let original = bytes.clone();
+        let options = CreateOptions {
+            force_override: true,
+            ..Default::default()
+        };
+        let (thumbs, hashes) = self
+            .create_thumb_images_from_bytes(
+                bytes,
+                Self::resolve_dest_dir(dest_dir, path)?,
+                &stem,
+                format,
+                options,
+            )
+            .await?;
+
+        // the exact paths, as they may depend on the contents of the image
+        let mut stored = thumbs
+            .iter()
+            .map(|thumb| Path::parse(Self::generate_path(&thumb.path, &thumb.stem, &thumb.format)))
+            .collect::<Result<Vec<_>, _>>()?;
+        stored.push(Path::parse(path)?);
+
+        self.upload(path, original).await?;
+        if let Err(e) = self.upload_thumbs(&self.client, thumbs, true).await {
+            if atomic {
+                self.roll_back(&stored).await?;
+            }
+            return Err(e);
+        }
+        Ok(hashes)
+    }
+
+    /// Deletes the objects of a failed [`ImageThumbs::ingest`], whether they were stored or not.
+    /// All objects are attempted; the first error is returned.
+    async fn roll_back(&self, paths: &[Path]) -> ThumbsResult<()> {
+        let mut result = Ok(());
+        for path in paths {
+            match self.retrying(|| self.client.delete(path)).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+                Err(err) if result.is_ok() => {
+                    result = Err(Error::from(err).context("rolling back", path));
+                }
+                Err(_) => {}
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use futures::TryStreamExt;
+    use image::ImageFormat;
+    use object_store::local::LocalFileSystem;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use object_store::ObjectStore;
+
+    use crate::model::Params;
+    use crate::test_utils::png;
+    use crate::{Error, ImageThumbs};
+
+    #[tokio::test]
+    async fn upload_and_download() {
+        let replica = Arc::new(InMemory::new());
+        let client =
+            ImageThumbs::from_parts(InMemory::new(), Vec::new()).with_replica(replica.clone());
+        let bytes = Bytes::from(png());
+
+        client
+            .upload("originals/penguin.png", bytes.clone())
+            .await
+            .unwrap();
+        client.wait_for_replication().await;
+        replica
+            .head(&Path::parse("originals/penguin.png").unwrap())
+            .await
+            .unwrap();
+
+        let (format, downloaded) = client.download("originals/penguin.png").await.unwrap();
+        assert_eq!(format, ImageFormat::Png);
+        assert_eq!(downloaded, bytes);
+
+        let result = client.download("originals/missing.png").await;
+        assert!(
+            matches!(result, Err(Error::SourceNotFound(path)) if path == "originals/missing.png")
+        );
+
+        client
+            .upload("originals/notes.txt", Bytes::from_static(b"no image"))
+            .await
+            .unwrap();
+        let error = client.download("originals/notes.txt").await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed detecting the format of originals/notes.txt: Image format not supported"
+        );
+        assert!(matches!(error.inner(), Error::NotSupported));
+    }
+
+    #[tokio::test]
+    async fn ingest() {
+        let settings = vec![Params {
+            name: "mini".to_string(),
+            size: (10, 10),
+            ..Default::default()
+        }];
+        let client = ImageThumbs::from_parts(InMemory::new(), settings);
+        let bytes = png();
+
+        client
+            .ingest(bytes, "originals/penguin.png", "thumbs", true)
+            .await
+            .unwrap();
+        for path in ["originals/penguin.png", "thumbs/penguin_mini.png"] {
+            client
+                .client
+                .head(&Path::parse(path).unwrap())
+                .await
+                .unwrap();
+        }
+
+        let result = client
+            .ingest(
+                b"not an image".to_vec(),
+                "originals/puffin.png",
+                "thumbs",
+                true,
+            )
+            .await;
+        assert!(result.is_err());
+        let result = client
+            .client
+            .head(&Path::parse("originals/puffin.png").unwrap())
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn roll_back_ingest() {
+        let root = std::env::temp_dir().join(format!("image_thumbs_ingest_{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let settings = vec![
+            Params {
+                name: "mini".to_string(),
+                size: (10, 10),
+                naming_pattern: Some("/{image_stem}_{source_hash}".to_string()),
+                ..Default::default()
+            },
+            Params {
+                name: "standard".to_string(),
+                size: (20, 20),
+                // cannot be stored, as `thumbs/blocked` is a file
+                naming_pattern: Some("/blocked/{image_stem}".to_string()),
+                ..Default::default()
+            },
+        ];
+        let client =
+            ImageThumbs::from_parts(LocalFileSystem::new_with_prefix(&root).unwrap(), settings);
+        let blocked = Path::parse("thumbs/blocked").unwrap();
+        client.client.put(&blocked, vec![0].into()).await.unwrap();
+        let bytes = png();
+
+        // deleting the thumbnail below the file fails as well
+        let error = client
+            .ingest(bytes, "originals/penguin.png", "thumbs", true)
+            .await
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Failed rolling back thumbs/blocked/penguin.png"));
+        let stored: Vec<_> = client
+            .client
+            .list(None)
+            .map_ok(|meta| meta.location)
+            .try_collect()
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(stored, [blocked]);
+    }
+}
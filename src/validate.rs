@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use object_store::path::Path;
+
+use crate::model::Params;
+use crate::{Error, ThumbsResult};
+
+/// Placeholders that can be used in naming patterns
+const PLACEHOLDERS: [&str; 8] = [
+    "{image_stem}",
+    "{thumb_name}",
+    "{width}",
+    "{height}",
+    "{format}",
+    "{quality}",
+    "{hash}",
+    "{source_hash}",
+];
+
+/// Checks the loaded presets, failing with [`Error::InvalidSettings`] that lists every problem
+/// together with the path of the field, e.g., `thumbs[1].quality`.
+pub(crate) fn validate(settings: &[Params]) -> ThumbsResult<()> {
+    let problems = problems("thumbs", settings);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidSettings(problems))
+    }
+}
+
+/// Problems of the presets stored under the given key, with the paths of the fields.
+pub(crate) fn problems(key: &str, settings: &[Params]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut names = HashMap::new();
+    for (index, params) in settings.iter().enumerate() {
+        let field = |name: &str| format!("{key}[{index}].{name}");
+
+        if params.name.is_empty() {
+            problems.push(format!("{}: must not be empty", field("name")));
+        } else if let Some(first) = names.insert(params.name.as_str(), index) {
+            problems.push(format!(
+                "{}: `{}` is already used by {key}[{first}]",
+                field("name"),
+                params.name
+            ));
+        }
+        if !(1..=100).contains(&params.quality) {
+            problems.push(format!(
+                "{}: must be between 1 and 100, got {}",
+                field("quality"),
+                params.quality
+            ));
+        }
+        if params.size.0 == 0 || params.size.1 == 0 {
+            problems.push(format!(
+                "{}: width and height must be greater than 0, got [{}, {}]",
+                field("size"),
+                params.size.0,
+                params.size.1
+            ));
+        }
+        if let Some((width, height)) = params.center {
+            if !(0.0..=1.0).contains(&width) || !(0.0..=1.0).contains(&height) {
+                problems.push(format!(
+                    "{}: must be between 0 and 1, got [{width}, {height}]",
+                    field("center")
+                ));
+            }
+        }
+        if let Some(dir) = &params.dest_dir {
+            if let Err(e) = Path::parse(dir) {
+                problems.push(format!("{}: {e}", field("dest_dir")));
+            }
+        }
+        if let Some(pattern) = &params.naming_pattern {
+            problems.extend(
+                naming_pattern_problems(pattern)
+                    .into_iter()
+                    .map(|problem| format!("{}: {problem}", field("naming_pattern"))),
+            );
+        }
+    }
+    problems
+}
+
+fn naming_pattern_problems(pattern: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    if !pattern.contains("{image_stem}") {
+        problems.push(
+            "must contain `{image_stem}`, otherwise the thumbnails of all images have the same name"
+                .to_string(),
+        );
+    }
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            problems.push("contains an unclosed `{`".to_string());
+            break;
+        };
+        let placeholder = &rest[start..start + end + 1];
+        if !PLACEHOLDERS.contains(&placeholder) {
+            problems.push(format!("contains the unknown placeholder `{placeholder}`"));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    if pattern.ends_with('/') {
+        problems.push("must not end with `/`".to_string());
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{Config, File, FileFormat};
+
+    use super::validate;
+    use crate::model::{Mode, Params};
+    use crate::Error;
+
+    #[test]
+    fn report_all_problems() {
+        let mut settings = vec![
+            Params::new("mini", (40, 40), Mode::Crop, 80),
+            Params::new("mini", (0, 40), Mode::Fit, 0),
+        ];
+        settings[0].naming_pattern = Some("/{thumb_name}/{stem}".to_string());
+        settings[1].center = Some((0.5, 1.5));
+        assert!(validate(&settings[..1]).is_err());
+
+        let Err(Error::InvalidSettings(problems)) = validate(&settings) else {
+            panic!("settings are invalid");
+        };
+        assert_eq!(
+            problems,
+            [
+                "thumbs[0].naming_pattern: must contain `{image_stem}`, otherwise the thumbnails \
+                 of all images have the same name",
+                "thumbs[0].naming_pattern: contains the unknown placeholder `{stem}`",
+                "thumbs[1].name: `mini` is already used by thumbs[0]",
+                "thumbs[1].quality: must be between 1 and 100, got 0",
+                "thumbs[1].size: width and height must be greater than 0, got [0, 40]",
+                "thumbs[1].center: must be between 0 and 1, got [0.5, 1.5]",
+            ]
+        );
+
+        settings[0].naming_pattern =
+            Some("/{thumb_name}/{image_stem}_{width}x{height}".to_string());
+        assert!(validate(&settings[..1]).is_ok());
+    }
+
+    #[test]
+    fn reject_unknown_fields() {
+        let config = "thumbs:\n  - name: mini\n    qualit: 80\n    size: [40, 40]\n    mode: crop";
+        let result = Config::builder()
+            .add_source(File::from_str(config, FileFormat::Yaml))
+            .build()
+            .unwrap()
+            .get::<Vec<Params>>("thumbs");
+        assert!(result.unwrap_err().to_string().contains("qualit"));
+    }
+}
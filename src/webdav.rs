@@ -0,0 +1,79 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http::header::AUTHORIZATION;
+use http::{HeaderMap, HeaderValue};
+use object_store::http::{HttpBuilder, HttpStore};
+
+use crate::model::Params;
+use crate::{ImageThumbs, ThumbsResult};
+
+/// Login for a WebDAV server using HTTP basic authentication.
+#[derive(Debug, Clone)]
+pub struct WebDavCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl ImageThumbs<HttpStore> {
+    /// Creates new ImageThumbs instance connected to a WebDAV server, e.g., an on-premise NAS.
+    ///
+    /// Reads the config file to know which thumbnails to create, see [`ImageThumbs::new`].
+    ///
+    /// # Arguments
+    /// * `url` - Base URL of the WebDAV share, all paths are relative to it
+    ///
+    /// * `credentials` - Login of the share, if it is not public
+    ///
+    /// * `config` - Path to the config file from the crate root (the extension may be omitted)
+    pub async fn new_webdav(
+        url: &str,
+        credentials: Option<WebDavCredentials>,
+        config: &str,
+    ) -> ThumbsResult<Self> {
+        Self::new_webdav_with_settings(url, credentials, Self::settings(config)?).await
+    }
+
+    pub async fn new_webdav_with_settings(
+        url: &str,
+        credentials: Option<WebDavCredentials>,
+        settings: Vec<Params>,
+    ) -> ThumbsResult<Self> {
+        let mut client_options = Self::client_options();
+        if let Some(credentials) = credentials {
+            let login =
+                STANDARD.encode(format!("{}:{}", credentials.username, credentials.password));
+            let mut authorization = HeaderValue::from_str(&format!("Basic {login}"))
+                .expect("base64 is a valid header value");
+            authorization.set_sensitive(true);
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, authorization);
+            client_options = client_options.with_default_headers(headers);
+        }
+        let client = HttpBuilder::new()
+            .with_url(url)
+            .with_client_options(client_options)
+            .build()?;
+
+        Ok(Self::from_parts(client, settings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ImageThumbs, WebDavCredentials};
+
+    #[tokio::test]
+    async fn webdav_with_credentials() {
+        let credentials = WebDavCredentials {
+            username: "user".to_string(),
+            password: "secret".to_string(),
+        };
+        ImageThumbs::new_webdav_with_settings(
+            "https://nas.example.com/images",
+            Some(credentials),
+            Vec::new(),
+        )
+        .await
+        .unwrap();
+    }
+}